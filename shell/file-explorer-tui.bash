@@ -0,0 +1,17 @@
+# cd-on-exit wrapper for file-explorer-tui.
+# Source this file in your ~/.bashrc and use `fet` instead of `file-explorer-tui`.
+# Quitting with q changes into the last directory, quitting with Q keeps the current one.
+fet() {
+    local cwd_file cwd exit_code
+    cwd_file="$(mktemp -t file-explorer-tui-cwd.XXXXXX)" || return
+    command file-explorer-tui --cwd-file "$cwd_file" "$@"
+    exit_code=$?
+    if [ -s "$cwd_file" ]; then
+        cwd="$(cat -- "$cwd_file")"
+        if [ -d "$cwd" ] && [ "$cwd" != "$PWD" ]; then
+            cd -- "$cwd" || exit_code=$?
+        fi
+    fi
+    rm -f -- "$cwd_file"
+    return "$exit_code"
+}
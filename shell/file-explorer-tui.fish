@@ -0,0 +1,16 @@
+# cd-on-exit wrapper for file-explorer-tui.
+# Source this file in your ~/.config/fish/config.fish and use `fet` instead of `file-explorer-tui`.
+# Quitting with q changes into the last directory, quitting with Q keeps the current one.
+function fet --wraps file-explorer-tui
+    set -l cwd_file (mktemp -t file-explorer-tui-cwd.XXXXXX); or return
+    command file-explorer-tui --cwd-file $cwd_file $argv
+    set -l exit_code $status
+    if test -s $cwd_file
+        set -l cwd (cat -- $cwd_file)
+        if test -d "$cwd"; and test "$cwd" != "$PWD"
+            cd -- $cwd
+        end
+    end
+    rm -f -- $cwd_file
+    return $exit_code
+end
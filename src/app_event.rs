@@ -0,0 +1,212 @@
+use std::{
+    io::{self, pipe, Error, PipeReader, PipeWriter, Write},
+    process::ExitStatus,
+    sync::{mpsc::Sender, Condvar, Mutex, OnceLock, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use crossterm::event::{poll, read, Event};
+use log::{error, info};
+
+use crate::{directory_loader::DirectoryLoadMessage, message::MessageLevel};
+
+/// Everything the main loop reacts to. All producers send into the same channel, so the main loop
+/// can block until anything happens instead of polling.
+pub enum AppEvent {
+    Terminal(Event),
+    Watcher(Result<notify::Event, notify::Error>),
+    DirectoryLoad {
+        load_id: u64,
+        message: DirectoryLoadMessage,
+    },
+    ShellCommandFinished {
+        command_line: String,
+        result: Result<(ExitStatus, String), Error>,
+    },
+    UiMessage(MessageLevel, String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ReaderState {
+    Reading,
+    PauseRequested,
+    Paused,
+    Stopped,
+}
+
+/// Handshake between the terminal event reader and programs that take over the terminal: the
+/// launcher requests a pause and waits until the reader has stopped reading, so no key press
+/// meant for the program is consumed by the reader.
+static READER_STATE: Mutex<ReaderState> = Mutex::new(ReaderState::Stopped);
+static READER_STATE_CHANGED: Condvar = Condvar::new();
+/// Wakes up the reader while it is blocked waiting for terminal input
+static READER_WAKER: OnceLock<PipeWriter> = OnceLock::new();
+
+/// Pauses reading terminal events and waits until the reader doesn't read anymore, or resumes
+/// reading them
+pub fn set_terminal_events_paused(paused: bool) {
+    let mut state = READER_STATE.lock().unwrap_or_else(PoisonError::into_inner);
+    if *state == ReaderState::Stopped {
+        return;
+    }
+    if !paused {
+        *state = ReaderState::Reading;
+        READER_STATE_CHANGED.notify_all();
+        return;
+    }
+
+    *state = ReaderState::PauseRequested;
+    if let Some(mut waker) = READER_WAKER.get() {
+        if let Err(error) = waker.write_all(&[0]) {
+            error!("Failed to wake up terminal event reader: {:?}", error);
+        }
+    }
+    let _state = READER_STATE_CHANGED
+        .wait_while(state, |state| *state == ReaderState::PauseRequested)
+        .unwrap_or_else(PoisonError::into_inner);
+}
+
+/// Blocks while the reader is paused. Acknowledges a requested pause first.
+fn wait_while_paused() {
+    let mut state = READER_STATE.lock().unwrap_or_else(PoisonError::into_inner);
+    if *state == ReaderState::PauseRequested {
+        *state = ReaderState::Paused;
+        READER_STATE_CHANGED.notify_all();
+    }
+    let _state = READER_STATE_CHANGED
+        .wait_while(state, |state| *state == ReaderState::Paused)
+        .unwrap_or_else(PoisonError::into_inner);
+}
+
+fn set_reader_state(new_state: ReaderState) {
+    let mut state = READER_STATE.lock().unwrap_or_else(PoisonError::into_inner);
+    *state = new_state;
+    READER_STATE_CHANGED.notify_all();
+}
+
+/// Reads terminal events on a separate thread and forwards them to the main loop. The thread
+/// blocks until there is input or it is woken up to pause.
+pub fn spawn_terminal_event_reader(sender: Sender<AppEvent>) {
+    let (mut wake_receiver, wake_sender) = match pipe() {
+        Ok(pipe) => pipe,
+        Err(error) => {
+            error!(
+                "Failed to create pipe for terminal event reader: {:?}",
+                error
+            );
+            return;
+        }
+    };
+    if READER_WAKER.set(wake_sender).is_err() {
+        error!("Terminal event reader is already running");
+        return;
+    }
+    set_reader_state(ReaderState::Reading);
+
+    thread::spawn(move || {
+        let result = read_terminal_events(&sender, &mut wake_receiver);
+        if let Err(error) = result {
+            error!("Failed to read terminal events: {:?}", error);
+        }
+        set_reader_state(ReaderState::Stopped);
+    });
+}
+
+fn read_terminal_events(
+    sender: &Sender<AppEvent>,
+    wake_receiver: &mut PipeReader,
+) -> io::Result<()> {
+    let terminal_input = open_terminal_input()?;
+    loop {
+        wait_while_paused();
+        if !wait_for_terminal_input(&terminal_input, wake_receiver)? {
+            continue;
+        }
+        // read everything that is available, crossterm may also have buffered several events
+        while poll(Duration::ZERO)? {
+            if sender.send(AppEvent::Terminal(read()?)).is_err() {
+                info!("Main loop is gone, stopping terminal event reader");
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Opens the terminal crossterm reads from: stdin, or the controlling terminal if stdin isn't one
+#[cfg(unix)]
+fn open_terminal_input() -> io::Result<Option<std::fs::File>> {
+    use std::io::IsTerminal;
+
+    if io::stdin().is_terminal() {
+        Ok(None)
+    } else {
+        std::fs::File::open("/dev/tty").map(Some)
+    }
+}
+
+/// Blocks until there is terminal input (true) or the reader is woken up (false)
+#[cfg(unix)]
+fn wait_for_terminal_input(
+    terminal_input: &Option<std::fs::File>,
+    wake_receiver: &mut PipeReader,
+) -> io::Result<bool> {
+    use std::{io::Read, os::fd::AsRawFd};
+
+    let terminal_fd = match terminal_input {
+        Some(file) => file.as_raw_fd(),
+        None => io::stdin().as_raw_fd(),
+    };
+    let mut poll_fds = [
+        libc::pollfd {
+            fd: terminal_fd,
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: wake_receiver.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+    // SAFETY: the pointer and length describe the array above, which outlives the call
+    let result = unsafe { libc::poll(poll_fds.as_mut_ptr(), poll_fds.len() as libc::nfds_t, -1) };
+    if result < 0 {
+        let error = io::Error::last_os_error();
+        return match error.kind() {
+            io::ErrorKind::Interrupted => Ok(false),
+            _ => Err(error),
+        };
+    }
+
+    // a pause request wins over input, the input might be meant for the program taking over
+    if poll_fds[1].revents != 0 {
+        // every pause request writes a single byte
+        wake_receiver.read_exact(&mut [0; 1])?;
+        return Ok(false);
+    }
+    Ok(poll_fds[0].revents != 0)
+}
+
+#[cfg(not(unix))]
+fn open_terminal_input() -> io::Result<()> {
+    Ok(())
+}
+
+/// Without poll(2) on the terminal and the pipe, pause requests are noticed after a timeout
+#[cfg(not(unix))]
+fn wait_for_terminal_input(
+    _terminal_input: &(),
+    _wake_receiver: &mut PipeReader,
+) -> io::Result<bool> {
+    let is_pause_requested =
+        || *READER_STATE.lock().unwrap_or_else(PoisonError::into_inner) != ReaderState::Reading;
+    loop {
+        if is_pause_requested() {
+            return Ok(false);
+        }
+        if poll(Duration::from_millis(50))? {
+            return Ok(!is_pause_requested());
+        }
+    }
+}
@@ -0,0 +1,342 @@
+/// Protects against typos like `{1..1000000}` creating a million files
+const MAX_EXPANDED_NAMES: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Literal(char),
+    BraceOpen,
+    Comma,
+    BraceClose,
+}
+
+impl Token {
+    fn to_char(self) -> char {
+        match self {
+            Token::Literal(char) => char,
+            Token::BraceOpen => '{',
+            Token::Comma => ',',
+            Token::BraceClose => '}',
+        }
+    }
+}
+
+/// Splits the input of the create prompt into names and brace expands every name, like a shell:
+/// `src/{lib,main}.rs` and `test_{1..3}.rs` result in two and three names. Names are separated by
+/// whitespace, quotes or a backslash keep spaces and braces literal.
+pub fn expand_input_names(input: &str) -> Result<Vec<String>, String> {
+    let mut names = vec![];
+    for word in split_into_words(input)? {
+        names.extend(expand_braces(&word)?);
+        if names.len() > MAX_EXPANDED_NAMES {
+            return Err(format!("Expands to more than {} names", MAX_EXPANDED_NAMES));
+        }
+    }
+    Ok(names)
+}
+
+/// Returns where the last name of the input starts and the name without its quotes and escapes,
+/// e.g. for completing it. It is empty if the input ends with whitespace.
+pub fn split_off_last_word(input: &str) -> (usize, String) {
+    let mut word_start = input.len();
+    let mut word = String::new();
+    let mut is_in_word = false;
+    let mut quote = None;
+    let mut chars = input.char_indices();
+
+    while let Some((index, char)) = chars.next() {
+        if quote.is_none() && !is_in_word && !char.is_whitespace() {
+            word_start = index;
+            is_in_word = true;
+        }
+        match (quote, char) {
+            (Some(quote_char), char) if char == quote_char => quote = None,
+            (Some(_), char) => word.push(char),
+            (None, '\'' | '"') => quote = Some(char),
+            (None, '\\') => word.push(chars.next().map_or('\\', |(_, char)| char)),
+            (None, char) if char.is_whitespace() => {
+                word.clear();
+                word_start = input.len();
+                is_in_word = false;
+            }
+            (None, char) => word.push(char),
+        }
+    }
+    (word_start, word)
+}
+
+/// Escapes every char with a special meaning in the input, so the name is created as it is
+pub fn escape_name(name: &str) -> String {
+    let mut escaped_name = String::with_capacity(name.len());
+    for char in name.chars() {
+        if char.is_whitespace() || matches!(char, '\'' | '"' | '\\' | '{' | ',' | '}') {
+            escaped_name.push('\\');
+        }
+        escaped_name.push(char);
+    }
+    escaped_name
+}
+
+fn split_into_words(input: &str) -> Result<Vec<Vec<Token>>, String> {
+    let mut words = vec![];
+    let mut word = vec![];
+    // quotes can also form an empty word, like ""
+    let mut is_in_word = false;
+    let mut quote = None;
+    let mut chars = input.chars();
+
+    while let Some(char) = chars.next() {
+        match (quote, char) {
+            (Some(quote_char), char) if char == quote_char => quote = None,
+            (Some(_), char) => word.push(Token::Literal(char)),
+            (None, '\'' | '"') => {
+                quote = Some(char);
+                is_in_word = true;
+            }
+            (None, '\\') => {
+                let escaped_char = chars.next().unwrap_or('\\');
+                word.push(Token::Literal(escaped_char));
+                is_in_word = true;
+            }
+            (None, char) if char.is_whitespace() => {
+                if is_in_word {
+                    words.push(std::mem::take(&mut word));
+                    is_in_word = false;
+                }
+            }
+            (None, char) => {
+                word.push(match char {
+                    '{' => Token::BraceOpen,
+                    ',' => Token::Comma,
+                    '}' => Token::BraceClose,
+                    char => Token::Literal(char),
+                });
+                is_in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return Err("Unclosed quote".to_string());
+    }
+    if is_in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Expands the first brace group of the word, then the groups of every result. Braces without a
+/// comma or a range, like `{x}`, are kept literally.
+fn expand_braces(word: &[Token]) -> Result<Vec<String>, String> {
+    for (open_index, token) in word.iter().enumerate() {
+        if *token != Token::BraceOpen {
+            continue;
+        }
+        let Some(close_index) = find_matching_brace_close(word, open_index) else {
+            continue;
+        };
+        let Some(alternatives) = get_brace_alternatives(&word[open_index + 1..close_index])? else {
+            continue;
+        };
+
+        let mut names = vec![];
+        for alternative in alternatives {
+            let expanded_word: Vec<Token> = word[..open_index]
+                .iter()
+                .chain(&alternative)
+                .chain(&word[close_index + 1..])
+                .copied()
+                .collect();
+            names.extend(expand_braces(&expanded_word)?);
+            if names.len() > MAX_EXPANDED_NAMES {
+                return Err(format!("Expands to more than {} names", MAX_EXPANDED_NAMES));
+            }
+        }
+        return Ok(names);
+    }
+
+    Ok(vec![word.iter().map(|token| token.to_char()).collect()])
+}
+
+fn find_matching_brace_close(word: &[Token], open_index: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, token) in word.iter().enumerate().skip(open_index) {
+        match token {
+            Token::BraceOpen => depth += 1,
+            Token::BraceClose => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the content of a brace group at its top level commas, or expands a range like `1..3`,
+/// `01..10` or `a..e`. None if it is neither.
+fn get_brace_alternatives(content: &[Token]) -> Result<Option<Vec<Vec<Token>>>, String> {
+    let mut alternatives = vec![vec![]];
+    let mut depth = 0;
+    for token in content {
+        match token {
+            Token::Comma if depth == 0 => {
+                alternatives.push(vec![]);
+                continue;
+            }
+            Token::BraceOpen => depth += 1,
+            Token::BraceClose => depth -= 1,
+            _ => {}
+        }
+        if let Some(alternative) = alternatives.last_mut() {
+            alternative.push(*token);
+        }
+    }
+    if alternatives.len() > 1 {
+        return Ok(Some(alternatives));
+    }
+
+    let content: String = content.iter().map(|token| token.to_char()).collect();
+    let range = expand_range(&content)?;
+    Ok(range.map(|values| {
+        values
+            .iter()
+            .map(|value| value.chars().map(Token::Literal).collect())
+            .collect()
+    }))
+}
+
+fn expand_range(content: &str) -> Result<Option<Vec<String>>, String> {
+    let Some((start, end)) = content.split_once("..") else {
+        return Ok(None);
+    };
+
+    if let (Ok(start_number), Ok(end_number)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        if start_number.abs_diff(end_number) >= MAX_EXPANDED_NAMES as u64 {
+            return Err(format!(
+                "{{{}}} expands to more than {} names",
+                content, MAX_EXPANDED_NAMES
+            ));
+        }
+        // a leading zero pads all numbers to the same width, like `01..10`
+        let is_zero_padded = |bound: &str| {
+            let digits = bound.trim_start_matches('-');
+            digits.len() > 1 && digits.starts_with('0')
+        };
+        let is_padded = is_zero_padded(start) || is_zero_padded(end);
+        let width = if is_padded {
+            start.len().max(end.len())
+        } else {
+            0
+        };
+        let numbers: Vec<i64> = if start_number <= end_number {
+            (start_number..=end_number).collect()
+        } else {
+            (end_number..=start_number).rev().collect()
+        };
+        return Ok(Some(
+            numbers
+                .iter()
+                .map(|number| format!("{:0width$}", number, width = width))
+                .collect(),
+        ));
+    }
+
+    let mut start_chars = start.chars();
+    let mut end_chars = end.chars();
+    match (
+        start_chars.next(),
+        start_chars.next(),
+        end_chars.next(),
+        end_chars.next(),
+    ) {
+        (Some(start_char), None, Some(end_char), None)
+            if start_char.is_ascii_alphabetic() && end_char.is_ascii_alphabetic() =>
+        {
+            let chars: Vec<char> = if start_char <= end_char {
+                (start_char..=end_char).collect()
+            } else {
+                (end_char..=start_char).rev().collect()
+            };
+            Ok(Some(chars.iter().map(|char| char.to_string()).collect()))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: &str) -> Vec<String> {
+        expand_input_names(input).unwrap()
+    }
+
+    #[test]
+    fn expands_alternatives() {
+        assert_eq!(expand("src/{lib,main}.rs"), ["src/lib.rs", "src/main.rs"]);
+        assert_eq!(expand("{a,b}{1,2}"), ["a1", "a2", "b1", "b2"]);
+        assert_eq!(expand("{a,b{c,d}}"), ["a", "bc", "bd"]);
+        assert_eq!(expand("x{,.bak}"), ["x", "x.bak"]);
+    }
+
+    #[test]
+    fn expands_ranges() {
+        assert_eq!(
+            expand("test_{1..3}.rs"),
+            ["test_1.rs", "test_2.rs", "test_3.rs"]
+        );
+        assert_eq!(expand("{3..1}"), ["3", "2", "1"]);
+        assert_eq!(expand("{-1..1}"), ["-1", "0", "1"]);
+        assert_eq!(expand("{c..a}"), ["c", "b", "a"]);
+        let zero_padded = expand("{01..10}");
+        assert_eq!(zero_padded.len(), 10);
+        assert_eq!(zero_padded[0], "01");
+        assert_eq!(zero_padded[9], "10");
+    }
+
+    #[test]
+    fn keeps_braces_without_alternatives() {
+        assert_eq!(expand("{x}"), ["{x}"]);
+        assert_eq!(expand("{}"), ["{}"]);
+        assert_eq!(expand("{a,b"), ["{a,b"]);
+        assert_eq!(expand("{1..b}"), ["{1..b}"]);
+    }
+
+    #[test]
+    fn splits_names_at_whitespace() {
+        assert_eq!(expand("  a  b/ "), ["a", "b/"]);
+        assert_eq!(expand(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn keeps_quoted_and_escaped_chars() {
+        assert_eq!(expand(r"my\ file.txt"), ["my file.txt"]);
+        assert_eq!(expand("'my file.txt' \"{a,b}\""), ["my file.txt", "{a,b}"]);
+        assert_eq!(expand(r"\{a,b\}"), ["{a,b}"]);
+        assert_eq!(expand("''"), [""]);
+    }
+
+    #[test]
+    fn rejects_unclosed_quotes() {
+        assert!(expand_input_names("'abc").is_err());
+        assert!(expand_input_names("a \"b").is_err());
+    }
+
+    #[test]
+    fn rejects_too_many_names() {
+        assert!(expand_input_names("{1..1001}").is_err());
+        assert!(expand_input_names("{1..100}{1..100}").is_err());
+        assert_eq!(expand("{1..1000}").len(), 1000);
+    }
+
+    #[test]
+    fn splits_off_the_last_word() {
+        assert_eq!(split_off_last_word("src/lib.rs ma"), (11, "ma".to_string()));
+        assert_eq!(split_off_last_word("a "), (2, String::new()));
+        assert_eq!(split_off_last_word(r"a my\ fi"), (2, "my fi".to_string()));
+        assert_eq!(split_off_last_word("a 'my fi"), (2, "my fi".to_string()));
+        assert_eq!(escape_name("my file{1,2}.txt"), r"my\ file\{1\,2\}.txt");
+    }
+}
@@ -0,0 +1,401 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{Error, ErrorKind},
+    path::Path,
+    process,
+};
+
+use chrono::{DateTime, Local};
+use regex::{Captures, Regex};
+
+use crate::file::File;
+
+pub struct BulkRenameEntry {
+    pub old_path: String,
+    pub new_path: String,
+    pub has_conflict: bool,
+}
+
+impl BulkRenameEntry {
+    pub fn is_unchanged(&self) -> bool {
+        self.old_path == self.new_path
+    }
+}
+
+/// A preview together with the inputs it was computed for
+pub struct BulkRenamePreview {
+    find: String,
+    replace: String,
+    pub entries: Result<Vec<BulkRenameEntry>, regex::Error>,
+}
+
+/// Returns the preview for the given inputs. It is only computed again if they changed since the
+/// cached preview was computed, not on every frame.
+pub fn get_bulk_rename_preview<'a>(
+    cached_preview: &'a mut Option<BulkRenamePreview>,
+    files: &[File],
+    find: &str,
+    replace: &str,
+) -> &'a BulkRenamePreview {
+    if cached_preview
+        .as_ref()
+        .is_some_and(|preview| preview.find != find || preview.replace != replace)
+    {
+        *cached_preview = None;
+    }
+    cached_preview.get_or_insert_with(|| BulkRenamePreview {
+        find: find.to_string(),
+        replace: replace.to_string(),
+        entries: compute_bulk_rename_preview(files, find, replace),
+    })
+}
+
+/// Computes the old -> new paths for every given file by applying the `find` regex to the file
+/// name and replacing all matches with `replace`.
+/// `replace` may contain capture group references like `$1` or `${name}` and the tokens `{n}`
+/// (counter starting at 1), `{ext}` (extension without the dot) and `{mtime}` (last modification
+/// date as YYYY-MM-DD).
+/// Entries whose new name is empty, contains a slash, is used more than once or already exists
+/// on disk are marked as conflicts. Existing files that are renamed away in the same batch are no
+/// conflict, so chains (a -> b, b -> c) and swaps work.
+pub fn compute_bulk_rename_preview(
+    files: &[File],
+    find: &str,
+    replace: &str,
+) -> Result<Vec<BulkRenameEntry>, regex::Error> {
+    let find_regex = Regex::new(find)?;
+    let replacement_parts = split_replacement(replace);
+
+    let mut entries: Vec<BulkRenameEntry> = files
+        .iter()
+        .enumerate()
+        .map(|(index, file)| {
+            let old_path = Path::new(&file.full_path);
+            let old_file_name = old_path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            let token_values = TokenValues::new(index + 1, old_path);
+            let new_file_name = find_regex
+                .replace_all(&old_file_name, |captures: &Captures| {
+                    let mut replacement = String::new();
+                    for part in &replacement_parts {
+                        match part {
+                            ReplacementPart::Template(template) => {
+                                captures.expand(template, &mut replacement)
+                            }
+                            ReplacementPart::Counter => replacement.push_str(&token_values.counter),
+                            ReplacementPart::Extension => {
+                                replacement.push_str(&token_values.extension)
+                            }
+                            ReplacementPart::ModificationDate => {
+                                replacement.push_str(&token_values.mtime)
+                            }
+                        }
+                    }
+                    replacement
+                })
+                .to_string();
+
+            let new_path = match old_path.parent() {
+                Some(parent) => parent.join(&new_file_name).to_string_lossy().to_string(),
+                None => new_file_name.clone(),
+            };
+
+            BulkRenameEntry {
+                has_conflict: new_file_name.is_empty() || new_file_name.contains('/'),
+                old_path: file.full_path.clone(),
+                new_path,
+            }
+        })
+        .collect();
+
+    let mut new_path_counts: HashMap<String, usize> = HashMap::new();
+    for entry in &entries {
+        *new_path_counts.entry(entry.new_path.clone()).or_insert(0) += 1;
+    }
+
+    let renamed_old_paths: HashSet<String> = entries
+        .iter()
+        .filter(|entry| !entry.is_unchanged())
+        .map(|entry| entry.old_path.clone())
+        .collect();
+
+    for entry in &mut entries {
+        if entry.is_unchanged() {
+            continue;
+        }
+        let is_duplicate = new_path_counts.get(&entry.new_path).copied().unwrap_or(0) > 1;
+        let already_exists =
+            !renamed_old_paths.contains(&entry.new_path) && Path::new(&entry.new_path).exists();
+        if is_duplicate || already_exists {
+            entry.has_conflict = true;
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Renames every changed entry. Every file is moved to a temporary name next to it first, so
+/// chains and swaps work regardless of the order of the entries. Existing files are never
+/// overwritten. Returned are the successfully renamed entries and the errors of the failed ones.
+pub fn apply_bulk_rename(
+    entries: &[BulkRenameEntry],
+) -> (Vec<&BulkRenameEntry>, Vec<(&BulkRenameEntry, Error)>) {
+    let mut renamed = vec![];
+    let mut failed = vec![];
+
+    let mut moved_entries = vec![];
+    for (index, entry) in entries
+        .iter()
+        .filter(|entry| !entry.is_unchanged())
+        .enumerate()
+    {
+        let temporary_path = get_temporary_path(&entry.old_path, index);
+        match fs::rename(&entry.old_path, &temporary_path) {
+            Ok(()) => moved_entries.push((entry, temporary_path)),
+            Err(error) => failed.push((entry, error)),
+        }
+    }
+
+    for (entry, temporary_path) in moved_entries {
+        // e.g. a file that should have been renamed away failed to move
+        let result = if Path::new(&entry.new_path).exists() {
+            Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", entry.new_path),
+            ))
+        } else {
+            fs::rename(&temporary_path, &entry.new_path)
+        };
+        match result {
+            Ok(()) => renamed.push(entry),
+            Err(error) => {
+                let error = match fs::rename(&temporary_path, &entry.old_path) {
+                    Ok(()) => error,
+                    Err(_) => Error::new(
+                        error.kind(),
+                        format!("{}, the file was left at {}", error, temporary_path),
+                    ),
+                };
+                failed.push((entry, error));
+            }
+        }
+    }
+    (renamed, failed)
+}
+
+/// Returns a hidden name next to the given path that no other file uses
+fn get_temporary_path(path: &str, index: usize) -> String {
+    let path = Path::new(path);
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let temporary_name = format!(".{}.bulk-rename-{}-{}", file_name, process::id(), index);
+    match path.parent() {
+        Some(parent) => parent.join(temporary_name).to_string_lossy().to_string(),
+        None => temporary_name,
+    }
+}
+
+/// A piece of the replacement. Tokens are split off before any capture group is expanded, so
+/// `$1{n}` stays group 1 followed by the counter and values are never expanded again.
+#[derive(Clone, Copy)]
+enum ReplacementPart<'a> {
+    /// Text with capture group references like `$1` or `${name}`
+    Template(&'a str),
+    Counter,
+    Extension,
+    ModificationDate,
+}
+
+fn split_replacement(replace: &str) -> Vec<ReplacementPart<'_>> {
+    let tokens = [
+        ("{n}", ReplacementPart::Counter),
+        ("{ext}", ReplacementPart::Extension),
+        ("{mtime}", ReplacementPart::ModificationDate),
+    ];
+
+    let mut parts = vec![];
+    let mut template_start = 0;
+    let mut index = 0;
+    while index < replace.len() {
+        let rest = &replace[index..];
+        // `$$` and `${name}` belong to the capture group syntax, `${n}` is the group named n
+        if rest.starts_with("$$") {
+            index += 2;
+            continue;
+        }
+        if rest.starts_with("${") {
+            index += rest.find('}').map_or(rest.len(), |end| end + 1);
+            continue;
+        }
+
+        let Some((token, part)) = tokens.iter().find(|(token, _)| rest.starts_with(token)) else {
+            index += rest.chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        if template_start < index {
+            parts.push(ReplacementPart::Template(&replace[template_start..index]));
+        }
+        parts.push(*part);
+        index += token.len();
+        template_start = index;
+    }
+    if template_start < replace.len() {
+        parts.push(ReplacementPart::Template(&replace[template_start..]));
+    }
+    parts
+}
+
+struct TokenValues {
+    counter: String,
+    extension: String,
+    mtime: String,
+}
+
+impl TokenValues {
+    fn new(counter: usize, path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let mtime = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .map(|modified| {
+                DateTime::<Local>::from(modified)
+                    .format("%Y-%m-%d")
+                    .to_string()
+            })
+            .unwrap_or_default();
+
+        TokenValues {
+            counter: counter.to_string(),
+            extension,
+            mtime,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file::get_file_for_path;
+
+    /// A directory for the files of one test, removed when the test ends
+    struct TestDirectory(std::path::PathBuf);
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Creates the given files in a new empty directory and returns them
+    fn create_files(test_name: &str, names: &[&str]) -> (TestDirectory, Vec<File>) {
+        let directory = std::env::temp_dir().join(format!(
+            "file-explorer-tui-bulk-rename-{}-{}",
+            process::id(),
+            test_name
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        let files = names
+            .iter()
+            .map(|name| {
+                let path = directory.join(name);
+                fs::write(&path, name).unwrap();
+                get_file_for_path(path.to_string_lossy().to_string())
+            })
+            .collect();
+        (TestDirectory(directory), files)
+    }
+
+    fn get_new_names(entries: &[BulkRenameEntry]) -> Vec<String> {
+        entries
+            .iter()
+            .map(|entry| {
+                Path::new(&entry.new_path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    fn get_conflicts(entries: &[BulkRenameEntry]) -> Vec<bool> {
+        entries.iter().map(|entry| entry.has_conflict).collect()
+    }
+
+    #[test]
+    fn replaces_capture_groups() {
+        let (_directory, files) = create_files("capture_groups", &["a.txt", "b.txt"]);
+        let entries = compute_bulk_rename_preview(&files, r"^(.*)\.txt$", "$1.md").unwrap();
+        assert_eq!(get_new_names(&entries), ["a.md", "b.md"]);
+        assert_eq!(get_conflicts(&entries), [false, false]);
+    }
+
+    #[test]
+    fn keeps_capture_groups_and_tokens_apart() {
+        let (_directory, files) = create_files("tokens", &["foo.rs", "bar.rs"]);
+        let entries = compute_bulk_rename_preview(&files, r"^(.*)\.rs$", "$1{n}.rs").unwrap();
+        assert_eq!(get_new_names(&entries), ["foo1.rs", "bar2.rs"]);
+        assert_eq!(get_conflicts(&entries), [false, false]);
+
+        let entries = compute_bulk_rename_preview(&files, r"^(.*)\.rs$", "$1{ext}").unwrap();
+        assert_eq!(get_new_names(&entries), ["foors", "barrs"]);
+    }
+
+    #[test]
+    fn keeps_named_groups_and_escaped_dollars() {
+        let (_directory, files) = create_files("named_groups", &["x.rs"]);
+        let entries =
+            compute_bulk_rename_preview(&files, r"^(?<n>.*)\.rs$", "${n}-$$-{n}").unwrap();
+        assert_eq!(get_new_names(&entries), ["x-$-1"]);
+    }
+
+    #[test]
+    fn does_not_expand_tokens_in_values() {
+        let (_directory, files) = create_files("token_values", &["a.{n}"]);
+        let entries = compute_bulk_rename_preview(&files, r"^a", "b.{ext}").unwrap();
+        assert_eq!(get_new_names(&entries), ["b.{n}.{n}"]);
+    }
+
+    #[test]
+    fn marks_duplicates_and_existing_files_as_conflicts() {
+        let (_directory, files) = create_files("conflicts", &["a1", "a2", "b"]);
+        let entries = compute_bulk_rename_preview(&files[..2], r"\d", "").unwrap();
+        assert_eq!(get_conflicts(&entries), [true, true]);
+
+        let entries = compute_bulk_rename_preview(&files[..1], r"a1", "b").unwrap();
+        assert_eq!(get_conflicts(&entries), [true]);
+
+        let entries = compute_bulk_rename_preview(&files[..1], r"a1", "x/y").unwrap();
+        assert_eq!(get_conflicts(&entries), [true]);
+    }
+
+    #[test]
+    fn allows_chains_and_swaps() {
+        // f0 -> f1, f1 -> f2
+        let (_directory, files) = create_files("chain", &["f0", "f1"]);
+        let entries = compute_bulk_rename_preview(&files, r"^f\d$", "f{n}").unwrap();
+        assert_eq!(get_new_names(&entries), ["f1", "f2"]);
+        assert_eq!(get_conflicts(&entries), [false, false]);
+
+        // f2 -> f1, f1 -> f2
+        let (_directory, files) = create_files("swap", &["f2", "f1"]);
+        let entries = compute_bulk_rename_preview(&files, r"^f\d$", "f{n}").unwrap();
+        assert_eq!(get_new_names(&entries), ["f1", "f2"]);
+        assert_eq!(get_conflicts(&entries), [false, false]);
+
+        let (renamed, failed) = apply_bulk_rename(&entries);
+        assert_eq!((renamed.len(), failed.len()), (2, 0));
+        assert_eq!(fs::read_to_string(&entries[0].new_path).unwrap(), "f2");
+        assert_eq!(fs::read_to_string(&entries[1].new_path).unwrap(), "f1");
+    }
+}
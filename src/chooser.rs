@@ -0,0 +1,27 @@
+use std::{
+    fs,
+    io::{stdout, Error, Write},
+};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ChooserMode {
+    None,
+    Files,
+    Directory,
+}
+
+/// Writes the chosen paths separated by newlines or NUL bytes to the given file, or to stdout if
+/// the path is "-"
+pub fn write_chosen_paths(paths: &[String], output_path: &str, use_nul: bool) -> Result<(), Error> {
+    let separator = if use_nul { "\0" } else { "\n" };
+    let mut output = paths.join(separator);
+    output.push_str(separator);
+
+    if output_path == "-" {
+        let mut stdout = stdout();
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()
+    } else {
+        fs::write(output_path, output)
+    }
+}
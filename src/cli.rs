@@ -0,0 +1,93 @@
+use clap::Parser;
+
+use crate::{chooser::ChooserMode, directory_watcher::WatcherMode, file::SortMode};
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Directory to start in. If a file is given, its directory is opened with the cursor on it.
+    /// Defaults to $HOME
+    pub path: Option<String>,
+
+    /// Show hidden files
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// How to sort files, directories are always listed first
+    #[arg(long, value_enum, default_value_t = SortMode::Name)]
+    pub sort: SortMode,
+
+    /// Path to the config file. Defaults to $XDG_CONFIG_HOME/file-explorer-tui/config.toml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<String>,
+
+    /// Disable all actions that create, delete, rename or otherwise modify files: creating,
+    /// deleting, renaming, editing, shell commands, opening a shell and foreground openers.
+    /// Detached openers still run.
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// On quit, write the final working directory to this file. Used by the shell wrappers in
+    /// `shell/` to cd into it
+    #[arg(long, value_name = "PATH")]
+    pub cwd_file: Option<String>,
+
+    /// File chooser mode: Enter on a file (or C on the 'Selected files' window) writes the chosen
+    /// absolute paths to PATH (stdout if omitted or "-") and exits. Quitting exits with code 1.
+    /// PATH needs an equals sign (--choose-files=PATH), so it isn't confused with the start path
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "-"
+    )]
+    pub choose_files: Option<String>,
+
+    /// Directory chooser mode: C writes the current directory (or in the 'Selected files' window
+    /// all selected directories) to PATH (stdout if omitted or "-") and exits. Quitting exits
+    /// with code 1. PATH needs an equals sign (--choose-dir=PATH)
+    #[arg(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "-",
+        conflicts_with = "choose_files"
+    )]
+    pub choose_dir: Option<String>,
+
+    /// Pre-fill the 'Selected files' window with the newline- or NUL-separated paths from this
+    /// file ("-" for stdin)
+    #[arg(long, value_name = "PATH")]
+    pub select_from: Option<String>,
+
+    /// Separate the chosen paths with NUL bytes instead of newlines
+    #[arg(long)]
+    pub print0: bool,
+
+    /// How directories are watched for changes, overrides `watcher` of the config
+    #[arg(long, value_enum, value_name = "MODE")]
+    pub watcher: Option<WatcherMode>,
+
+    /// How often polled directories are checked for changes, overrides `poll_interval_ms` of the
+    /// config
+    #[arg(long, value_name = "MS")]
+    pub poll_interval: Option<u64>,
+}
+
+impl Cli {
+    pub fn get_chooser_mode(&self) -> ChooserMode {
+        if self.choose_files.is_some() {
+            ChooserMode::Files
+        } else if self.choose_dir.is_some() {
+            ChooserMode::Directory
+        } else {
+            ChooserMode::None
+        }
+    }
+
+    pub fn get_chooser_output_path(&self) -> Option<&String> {
+        self.choose_files.as_ref().or(self.choose_dir.as_ref())
+    }
+}
@@ -1,13 +1,22 @@
 use std::{
     io::Error,
-    process::{Command, Output},
+    process::{Command, ExitStatus, Stdio},
+    thread,
+};
+
+use log::warn;
+
+use crate::{
+    app_event::set_terminal_events_paused,
+    env::{get_editor, get_shell},
+    tui::{restore_tui, resume_tui},
 };
 
 const LINUX_OPEN: &str = "xdg-open";
 const MACOS_OPEN: &str = "open";
 const WINDOWS_OPEN: &str = "start";
 
-fn get_open_command_for_system_arch() -> &'static str {
+pub fn get_open_command_for_system_arch() -> &'static str {
     if cfg!(windows) {
         WINDOWS_OPEN
     } else if cfg!(target_os = "linux") {
@@ -19,11 +28,157 @@ fn get_open_command_for_system_arch() -> &'static str {
     }
 }
 
-pub fn open_file_with_system_app(file_path: &str) -> Result<Output, Error> {
-    let open_command = get_open_command_for_system_arch();
+/// Leaves the TUI, runs the given command in the foreground with the terminal attached and
+/// enters the TUI again once the command exits. The caller is responsible for redrawing the
+/// whole terminal afterwards.
+pub fn run_command_in_foreground(command: &mut Command) -> Result<ExitStatus, Error> {
+    set_terminal_events_paused(true);
+    restore_tui();
+    let status = command.status();
+    let resume_result = resume_tui();
+    set_terminal_events_paused(false);
+    resume_result?;
+    status
+}
+
+pub fn open_file_in_editor(file_path: &str) -> Result<ExitStatus, Error> {
+    let editor = get_editor();
+    // $EDITOR may contain arguments, e.g. "code --wait"
+    let mut editor_parts = editor.split_whitespace();
+    let editor_program = editor_parts.next().unwrap_or("vi");
+
+    let mut command = Command::new(editor_program);
+    command.args(editor_parts).arg(file_path);
+    run_command_in_foreground(&mut command)
+}
+
+/// Set in the environment of shells started from the explorer, so prompts and scripts can tell
+/// they are nested inside of it
+pub const NESTED_SHELL_ENV_VAR: &str = "FILE_EXPLORER_TUI_NESTED";
+
+/// Suspends the TUI and starts an interactive shell in the given directory
+pub fn open_shell_in_directory(directory: &str) -> Result<ExitStatus, Error> {
+    let mut command = Command::new(get_shell());
+    command
+        .current_dir(directory)
+        .env(NESTED_SHELL_ENV_VAR, "1");
+    run_command_in_foreground(&mut command)
+}
+
+/// Quotes the given string so it is passed as a single argument by the shell
+pub fn shell_quote(value: &str) -> String {
+    if cfg!(windows) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
 
-    let mut new_command = Command::new(open_command);
-    let arg_added = new_command.arg(file_path);
+/// Creates a command that runs the given command line with the system shell
+pub fn create_shell_command(command_line: &str) -> Command {
+    if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(command_line);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(command_line);
+        command
+    }
+}
+
+/// Starts the given command without waiting for it and without connecting it to the terminal.
+/// It is waited for on a separate thread, so it doesn't stay a zombie process after it exits.
+pub fn spawn_detached(command: &mut Command) -> Result<(), Error> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || {
+        if let Err(error) = child.wait() {
+            warn!(
+                "Failed to wait for detached process {}: {:?}",
+                child.id(),
+                error
+            );
+        }
+    });
+    Ok(())
+}
+
+/// Detects the MIME type of the given file with the `file` utility, if available
+pub fn detect_mime_type(file_path: &str) -> Option<String> {
+    let output = Command::new("file")
+        .arg("--brief")
+        .arg("--mime-type")
+        .arg(file_path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mime_type = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if mime_type.is_empty() {
+        None
+    } else {
+        Some(mime_type)
+    }
+}
+
+/// Replaces the placeholders `%f` (current file), `%s` (all selected files), `%d` (directory) and
+/// `%%` (a literal %) in the given command line with shell-quoted values.
+pub fn expand_command_placeholders(
+    command_line: &str,
+    current_file: Option<&str>,
+    selected_files: &[String],
+    directory: &str,
+) -> String {
+    let mut expanded = String::new();
+    let mut chars = command_line.chars().peekable();
+    while let Some(char) = chars.next() {
+        if char != '%' {
+            expanded.push(char);
+            continue;
+        }
+        match chars.peek() {
+            Some('f') => {
+                expanded.push_str(&current_file.map(shell_quote).unwrap_or_default());
+                chars.next();
+            }
+            Some('s') => {
+                let quoted_selected_files: Vec<String> = selected_files
+                    .iter()
+                    .map(|file| shell_quote(file))
+                    .collect();
+                expanded.push_str(&quoted_selected_files.join(" "));
+                chars.next();
+            }
+            Some('d') => {
+                expanded.push_str(&shell_quote(directory));
+                chars.next();
+            }
+            Some('%') => {
+                expanded.push('%');
+                chars.next();
+            }
+            _ => expanded.push(char),
+        }
+    }
+    expanded
+}
 
-    arg_added.output()
+/// Runs the given command line with the system shell in the given directory and returns the
+/// combined stdout and stderr
+pub fn run_shell_command_with_output(
+    command_line: &str,
+    directory: &str,
+) -> Result<(ExitStatus, String), Error> {
+    let output = create_shell_command(command_line)
+        .current_dir(directory)
+        .stdin(Stdio::null())
+        .output()?;
+    let mut combined_output = String::from_utf8_lossy(&output.stdout).to_string();
+    combined_output.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok((output.status, combined_output))
 }
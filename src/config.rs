@@ -0,0 +1,76 @@
+use std::{fs, io::ErrorKind};
+
+use serde::Deserialize;
+
+use crate::{directory_watcher::WatcherMode, env::get_config_dir};
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub openers: Vec<OpenerRule>,
+    /// how many listings of recently visited directories are kept in memory
+    pub directory_cache_size: usize,
+    /// watcher events are collected for this long and then applied together
+    pub watcher_debounce_ms: u64,
+    /// how directories are watched for changes, "auto" polls network and FUSE file systems
+    pub watcher: WatcherMode,
+    /// how often polled directories are checked for changes
+    pub poll_interval_ms: u64,
+    /// how long created or modified files are highlighted, 0 disables highlighting
+    pub highlight_changes_ms: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            openers: vec![],
+            directory_cache_size: 16,
+            watcher_debounce_ms: 100,
+            watcher: WatcherMode::Auto,
+            poll_interval_ms: 2000,
+            highlight_changes_ms: 3000,
+        }
+    }
+}
+
+/// A rule mapping files to a command that can open them. A rule matches a file if all of the
+/// given criteria match; a rule without any criteria matches every file.
+#[derive(Deserialize, Clone)]
+pub struct OpenerRule {
+    pub name: Option<String>,
+    /// glob matched against the file name, or the full path if the glob contains a slash
+    pub glob: Option<String>,
+    /// extensions without the leading dot, compared case-insensitive
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// MIME type pattern like "text/plain" or "image/*"
+    pub mime: Option<String>,
+    /// command template, `%f` is replaced with the file path. Without `%f` the path is appended
+    pub command: String,
+    /// run the command in the terminal (e.g. for terminal editors or pagers) instead of
+    /// detaching it
+    #[serde(default)]
+    pub foreground: bool,
+}
+
+impl OpenerRule {
+    pub fn get_display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.command)
+    }
+}
+
+pub fn get_default_config_path() -> Option<String> {
+    get_config_dir()
+        .ok()
+        .map(|config_dir| config_dir + "/config.toml")
+}
+
+/// Loads the config from the given path. A missing config file results in the default config.
+pub fn load_config(config_path: &str) -> Result<Config, String> {
+    match fs::read_to_string(config_path) {
+        Ok(content) => toml::from_str(&content)
+            .map_err(|error| format!("Failed to parse config {}: {}", config_path, error)),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(Config::default()),
+        Err(error) => Err(format!("Failed to read config {}: {}", config_path, error)),
+    }
+}
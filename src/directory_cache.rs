@@ -0,0 +1,81 @@
+use std::collections::VecDeque;
+
+use crate::file::{File, SortMode};
+
+struct CachedDirectory {
+    directory: String,
+    files: Vec<File>,
+    show_hidden_files: bool,
+    sort_mode: SortMode,
+}
+
+/// LRU cache of the listings of recently visited directories. The listing of the working
+/// directory is taken out of the cache while it is the working directory and put back when
+/// leaving it, so the file list doesn't need to be cloned.
+pub struct DirectoryCache {
+    capacity: usize,
+    /// least recently used first
+    entries: VecDeque<CachedDirectory>,
+}
+
+impl DirectoryCache {
+    pub fn new(capacity: usize) -> DirectoryCache {
+        DirectoryCache {
+            capacity,
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Removes the listing of the given directory from the cache and returns it, if it was listed
+    /// with the same options
+    pub fn take(
+        &mut self,
+        directory: &str,
+        show_hidden_files: bool,
+        sort_mode: SortMode,
+    ) -> Option<Vec<File>> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.directory == directory)?;
+        let entry = self.entries.remove(index)?;
+        if entry.show_hidden_files == show_hidden_files && entry.sort_mode == sort_mode {
+            Some(entry.files)
+        } else {
+            None
+        }
+    }
+
+    /// Caches the listing of the given directory. Returned are the directories that were evicted
+    /// to make room for it.
+    pub fn insert(
+        &mut self,
+        directory: String,
+        files: Vec<File>,
+        show_hidden_files: bool,
+        sort_mode: SortMode,
+    ) -> Vec<String> {
+        self.entries.retain(|entry| entry.directory != directory);
+        self.entries.push_back(CachedDirectory {
+            directory,
+            files,
+            show_hidden_files,
+            sort_mode,
+        });
+
+        let mut evicted_directories = vec![];
+        while self.entries.len() > self.capacity {
+            if let Some(evicted) = self.entries.pop_front() {
+                evicted_directories.push(evicted.directory);
+            }
+        }
+        evicted_directories
+    }
+
+    /// Removes the listing of the given directory. Returns true if it was cached.
+    pub fn invalidate(&mut self, directory: &str) -> bool {
+        let previous_len = self.entries.len();
+        self.entries.retain(|entry| entry.directory != directory);
+        self.entries.len() != previous_len
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    fs,
+    io::Error,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::info;
+
+use crate::{
+    app_event::AppEvent,
+    file::{get_file_for_dir_entry, File},
+};
+
+const BATCH_SIZE: usize = 1000;
+const BATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+static NEXT_LOAD_ID: AtomicU64 = AtomicU64::new(0);
+
+pub enum DirectoryLoadMessage {
+    Batch(Vec<File>),
+    Finished,
+    Failed(Error),
+}
+
+/// A directory listing running on a worker thread. Dropping it cancels the listing.
+/// The worker thread sends its results as [`AppEvent::DirectoryLoad`] with the id of the load, so
+/// results of stale loads can be told apart.
+pub struct DirectoryLoad {
+    pub id: u64,
+    pub directory: String,
+    pub started_at: Instant,
+    pub loaded_count: usize,
+    /// When true, the entries are streamed into the file list while loading (e.g. when entering a
+    /// directory). Otherwise they are collected and replace the file list once the listing is
+    /// finished (e.g. when refreshing the current directory).
+    pub stream_into_files: bool,
+    pub loaded_files: Vec<File>,
+    /// Selected once the listing is finished, the path takes precedence over the index
+    pub path_to_select: Option<String>,
+    pub index_to_select: Option<usize>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Drop for DirectoryLoad {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+pub fn start_directory_load(
+    directory: String,
+    show_hidden_files: bool,
+    stream_into_files: bool,
+    sender: Sender<AppEvent>,
+) -> DirectoryLoad {
+    let id = NEXT_LOAD_ID.fetch_add(1, Ordering::Relaxed);
+    let cancelled = Arc::new(AtomicBool::new(false));
+
+    let thread_directory = directory.clone();
+    let thread_cancelled = cancelled.clone();
+    thread::spawn(move || {
+        let send = |message| {
+            sender
+                .send(AppEvent::DirectoryLoad {
+                    load_id: id,
+                    message,
+                })
+                .is_ok()
+        };
+        load_directory(
+            &thread_directory,
+            show_hidden_files,
+            send,
+            &thread_cancelled,
+        )
+    });
+
+    DirectoryLoad {
+        id,
+        directory,
+        started_at: Instant::now(),
+        loaded_count: 0,
+        stream_into_files,
+        loaded_files: vec![],
+        path_to_select: None,
+        index_to_select: None,
+        cancelled,
+    }
+}
+
+fn load_directory(
+    directory: &str,
+    show_hidden_files: bool,
+    send: impl Fn(DirectoryLoadMessage) -> bool,
+    cancelled: &AtomicBool,
+) {
+    let read_dir = match fs::read_dir(directory) {
+        Ok(read_dir) => read_dir,
+        Err(error) => {
+            send(DirectoryLoadMessage::Failed(error));
+            return;
+        }
+    };
+
+    let mut batch: Vec<File> = Vec::with_capacity(BATCH_SIZE);
+    let mut last_batch_sent_at = Instant::now();
+    for dir_entry in read_dir.flatten() {
+        let file = get_file_for_dir_entry(&dir_entry);
+        if show_hidden_files || !file.display_name.starts_with(".") {
+            batch.push(file);
+        }
+
+        if batch.len() >= BATCH_SIZE || last_batch_sent_at.elapsed() >= BATCH_INTERVAL {
+            if cancelled.load(Ordering::Relaxed) {
+                info!("Cancelled loading of directory {:?}", directory);
+                return;
+            }
+            // the main loop is gone, nothing left to do then
+            if !send(DirectoryLoadMessage::Batch(std::mem::take(&mut batch))) {
+                return;
+            }
+            last_batch_sent_at = Instant::now();
+        }
+    }
+
+    if !batch.is_empty() {
+        send(DirectoryLoadMessage::Batch(batch));
+    }
+    send(DirectoryLoadMessage::Finished);
+}
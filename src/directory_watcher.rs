@@ -1,53 +1,320 @@
-use std::{path::Path, sync::mpsc::Receiver};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::Sender,
+    time::{Duration, Instant},
+};
 
+use clap::ValueEnum;
 use log::{info, warn};
 use notify::{
-    event::ModifyKind,
+    event::{MetadataKind, ModifyKind},
     Config, Error, Event,
     EventKind::{Create, Modify, Remove},
-    INotifyWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+    PollWatcher, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use serde::Deserialize;
+
+use crate::{
+    app_event::AppEvent,
+    file::{apply_file_changes, get_file_for_path},
+    mounts::is_on_remote_or_fuse_file_system,
+    utils::{
+        get_selected_file_path, recover_from_missing_working_directory,
+        refresh_files_for_working_directory, select_file_by_path,
+    },
+    AppState,
 };
 
-use crate::{utils::refresh_files_for_working_directory, AppState};
+/// Above this many changed entries a full refresh is cheaper than applying them one by one
+const MAX_INCREMENTAL_CHANGES: usize = 1000;
 
-pub fn setup_directory_watcher(
-    initial_directory: String,
-) -> (INotifyWatcher, Receiver<Result<notify::Event, Error>>) {
-    info!("Setting up directory watcher...");
-    let (sender, receiver) = std::sync::mpsc::channel();
+#[derive(Clone, Copy, PartialEq, Debug, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum WatcherMode {
+    /// Poll directories on network and FUSE file systems, use native events everywhere else
+    #[default]
+    Auto,
+    /// Always use native file system events (e.g. inotify)
+    Native,
+    /// Always poll, e.g. for network file systems that aren't detected
+    Poll,
+}
+
+/// Watches the working directory and the cached directories, non-recursively. Depending on the
+/// watcher mode and the file system, a directory is either watched via native file system events
+/// or polled.
+pub struct DirectoryWatcher {
+    mode: WatcherMode,
+    poll_interval: Duration,
+    /// None if the native watcher couldn't be set up, everything is polled then
+    native_watcher: Option<Box<dyn Watcher>>,
+    /// Only created once the first directory needs to be polled, as it runs its own thread
+    poll_watcher: Option<Box<dyn Watcher>>,
+    app_event_sender: Sender<AppEvent>,
+    /// The watched directories and whether they are polled
+    watched_directories: HashMap<String, bool>,
+}
+
+impl DirectoryWatcher {
+    pub fn watch(&mut self, directory: &str) {
+        if self.watched_directories.contains_key(directory) {
+            return;
+        }
 
-    // we pass in the sender so notify can send events
-    let mut watcher = RecommendedWatcher::new(sender, Config::default())
-        .expect("Can setup recommended_watcher from notify");
+        let should_poll = match self.mode {
+            WatcherMode::Auto => is_on_remote_or_fuse_file_system(directory),
+            WatcherMode::Native => false,
+            WatcherMode::Poll => true,
+        };
+        if !should_poll {
+            match self.watch_natively(directory) {
+                Ok(()) => {
+                    info!("Successfully watching directory: {:?}", directory);
+                    self.watched_directories
+                        .insert(directory.to_string(), false);
+                    return;
+                }
+                // e.g. when the inotify watch limit is reached
+                Err(error) => warn!(
+                    "Failed to watch directory {:?} natively, polling it instead: {:?}",
+                    directory, error
+                ),
+            }
+        }
 
-    info!("Trying to watch given directory in non recursive mode");
-    let result = watcher.watch(Path::new(&initial_directory), RecursiveMode::NonRecursive);
-    match result {
-        Ok(()) => {
-            info!(
-                "Successfully setup directory watcher for directory {:?} with non-recursive mode.",
-                initial_directory
-            )
+        match self.watch_by_polling(directory) {
+            Ok(()) => {
+                info!("Successfully polling directory: {:?}", directory);
+                self.watched_directories.insert(directory.to_string(), true);
+            }
+            Err(error) => {
+                warn!("Failed to watch directory {:?}: {:?}", directory, error)
+            }
         }
-        Err(err) => {
-            warn!("Failed to setup directory watcher: {:?}", err)
+    }
+
+    pub fn unwatch(&mut self, directory: &str) {
+        let Some(is_polled) = self.watched_directories.remove(directory) else {
+            return;
+        };
+        let watcher = if is_polled {
+            &mut self.poll_watcher
+        } else {
+            &mut self.native_watcher
+        };
+        let Some(watcher) = watcher else {
+            return;
+        };
+        if let Err(error) = watcher.unwatch(Path::new(directory)) {
+            warn!("Failed to unwatch directory {:?}: {:?}", directory, error);
         }
     }
-    // We need to return the watcher too, otherwise it will be dropped and the receiver is
-    // disconnected
-    (watcher, receiver)
+
+    /// Describes how the given directory is watched, e.g. for showing it in the UI
+    pub fn get_watch_description(&self, directory: &str) -> String {
+        match self.watched_directories.get(directory) {
+            Some(false) => String::from("native events"),
+            Some(true) => format!("polling every {}ms", self.poll_interval.as_millis()),
+            None => String::from("not watched"),
+        }
+    }
+
+    fn watch_natively(&mut self, directory: &str) -> Result<(), Error> {
+        let Some(native_watcher) = &mut self.native_watcher else {
+            return Err(Error::generic("native watcher is not available"));
+        };
+        native_watcher.watch(Path::new(directory), RecursiveMode::NonRecursive)
+    }
+
+    fn watch_by_polling(&mut self, directory: &str) -> Result<(), Error> {
+        let poll_watcher = match &mut self.poll_watcher {
+            Some(poll_watcher) => poll_watcher,
+            None => {
+                let config = Config::default().with_poll_interval(self.poll_interval);
+                let poll_watcher = PollWatcher::new(
+                    forward_watcher_events(self.app_event_sender.clone()),
+                    config,
+                )?;
+                self.poll_watcher.insert(Box::new(poll_watcher))
+            }
+        };
+        poll_watcher.watch(Path::new(directory), RecursiveMode::NonRecursive)
+    }
 }
 
+pub fn setup_directory_watcher(
+    initial_directory: &str,
+    mode: WatcherMode,
+    poll_interval: Duration,
+    app_event_sender: Sender<AppEvent>,
+) -> DirectoryWatcher {
+    info!("Setting up directory watcher in {:?} mode...", mode);
+
+    let native_watcher: Option<Box<dyn Watcher>> = match mode {
+        WatcherMode::Poll => None,
+        WatcherMode::Auto | WatcherMode::Native => {
+            let result = RecommendedWatcher::new(
+                forward_watcher_events(app_event_sender.clone()),
+                Config::default(),
+            );
+            match result {
+                Ok(native_watcher) => Some(Box::new(native_watcher)),
+                Err(error) => {
+                    warn!(
+                        "Failed to set up native watcher, polling instead: {:?}",
+                        error
+                    );
+                    None
+                }
+            }
+        }
+    };
+
+    let mut directory_watcher = DirectoryWatcher {
+        mode,
+        poll_interval,
+        native_watcher,
+        poll_watcher: None,
+        app_event_sender,
+        watched_directories: HashMap::new(),
+    };
+    directory_watcher.watch(initial_directory);
+    directory_watcher
+}
+
+/// notify calls this from its own thread, we forward everything to the main loop
+fn forward_watcher_events(
+    app_event_sender: Sender<AppEvent>,
+) -> impl Fn(Result<Event, Error>) + Send + 'static {
+    move |result| {
+        let _ = app_event_sender.send(AppEvent::Watcher(result));
+    }
+}
+
+/// Collects the paths changed by the given event. They are applied together once the debounce
+/// window is over, so bursts of events (e.g. from `git checkout`) only cause a single update.
+/// Created and modified files are remembered for highlighting them, every event is added to the
+/// event log.
 pub fn handle_notify_watcher_event(event: Event, app_state: &mut AppState) {
-    match event.kind {
-        Create(_) | Remove(_) => {
-            refresh_files_for_working_directory(app_state);
+    app_state.event_log.push(&event);
+
+    let changes_listing = matches!(
+        event.kind,
+        Create(_) | Remove(_) | Modify(ModifyKind::Name(_))
+    );
+    // the poll watcher only notices content changes through the modification time
+    let changes_content = matches!(
+        event.kind,
+        Modify(ModifyKind::Data(_)) | Modify(ModifyKind::Metadata(MetadataKind::WriteTime))
+    );
+    if !changes_listing && !changes_content {
+        return;
+    }
+
+    if matches!(event.kind, Create(_)) || changes_content {
+        for path in &event.paths {
+            let path = path.to_string_lossy().to_string();
+            app_state.recent_changes.record(path);
         }
-        Modify(modify_kind) => {
-            if let ModifyKind::Name(_) = modify_kind {
-                refresh_files_for_working_directory(app_state);
-            }
+    }
+
+    // content changes matter too, size and modification time are cached with the files, shown and
+    // might be sorted by
+    let changed_paths = event.paths;
+    if changed_paths.is_empty() {
+        return;
+    }
+
+    if app_state.pending_watcher_paths_since.is_none() {
+        app_state.pending_watcher_paths_since = Some(Instant::now());
+    }
+    app_state.pending_watcher_paths.extend(changed_paths);
+}
+
+/// Returns how long until the pending watcher changes are due, None if there are none
+pub fn get_time_until_pending_watcher_changes_are_due(app_state: &AppState) -> Option<Duration> {
+    app_state.pending_watcher_paths_since.map(|since| {
+        Duration::from_millis(app_state.config.watcher_debounce_ms).saturating_sub(since.elapsed())
+    })
+}
+
+/// Applies the collected watcher changes once the debounce window is over. Changes in the working
+/// directory are applied entry by entry, cached directories are invalidated, also when only the
+/// content of one of their files changed.
+/// Returns true if changes were applied.
+pub fn apply_pending_watcher_changes_if_due(app_state: &mut AppState) -> bool {
+    if get_time_until_pending_watcher_changes_are_due(app_state) != Some(Duration::ZERO) {
+        return false;
+    }
+    app_state.pending_watcher_paths_since = None;
+    let changed_paths = std::mem::take(&mut app_state.pending_watcher_paths);
+
+    let mut changed_paths_in_working_directory: Vec<PathBuf> = vec![];
+    let mut affected_directories: HashSet<String> = HashSet::new();
+    for changed_path in changed_paths {
+        let Some(parent) = changed_path.parent() else {
+            continue;
+        };
+        let parent = parent.to_string_lossy().to_string();
+        if parent == app_state.working_directory {
+            changed_paths_in_working_directory.push(changed_path.clone());
+        } else {
+            affected_directories.insert(parent);
+        }
+        // the changed path might be a cached directory that was removed or renamed itself
+        affected_directories.insert(changed_path.to_string_lossy().to_string());
+    }
+
+    for affected_directory in affected_directories {
+        if app_state.directory_cache.invalidate(&affected_directory) {
+            // it is listed again when it is visited next time, no need to watch it until then
+            info!("Invalidated cached listing of {:?}", affected_directory);
+            app_state.directory_watcher.unwatch(&affected_directory);
         }
-        _ => {}
     }
+
+    // e.g. the working directory itself or one of its parents was removed or renamed
+    if recover_from_missing_working_directory(app_state) {
+        return true;
+    }
+    if changed_paths_in_working_directory.is_empty() {
+        return true;
+    }
+    // a running listing might or might not have seen the changes already
+    if app_state.directory_load.is_some()
+        || changed_paths_in_working_directory.len() > MAX_INCREMENTAL_CHANGES
+    {
+        refresh_files_for_working_directory(app_state);
+        return true;
+    }
+
+    // inserting or removing entries above the cursor must not move it to another file
+    let selected_path = get_selected_file_path(app_state);
+
+    // the events only tell us that something happened to the path, the file system tells us what
+    // the result is
+    let mut changed_full_paths: HashSet<String> = HashSet::new();
+    let mut new_files = vec![];
+    for changed_path in changed_paths_in_working_directory {
+        let full_path = changed_path.to_string_lossy().to_string();
+        changed_full_paths.insert(full_path.clone());
+        if changed_path.symlink_metadata().is_err() {
+            continue;
+        }
+        let file = get_file_for_path(full_path);
+        if app_state.show_hidden_files || !file.display_name.starts_with(".") {
+            new_files.push(file);
+        }
+    }
+    apply_file_changes(
+        &mut app_state.files,
+        &changed_full_paths,
+        new_files,
+        app_state.sort_mode,
+    );
+    if let Some(selected_path) = selected_path {
+        select_file_by_path(app_state, &selected_path);
+    }
+    true
 }
@@ -1,5 +1,82 @@
-use std::env::{self, VarError};
+use std::{
+    env::{self, VarError},
+    path::PathBuf,
+};
 
 pub fn get_home_dir() -> Result<String, VarError> {
     env::var("HOME")
 }
+
+/// Returns the editor set via $VISUAL or $EDITOR, falling back to vi
+pub fn get_editor() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"))
+}
+
+/// Returns the config directory of the explorer, respecting $XDG_CONFIG_HOME
+pub fn get_config_dir() -> Result<String, VarError> {
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|config_home| !config_home.is_empty())
+        .map(Ok)
+        .unwrap_or_else(|| get_home_dir().map(|home_dir| home_dir + "/.config"))?;
+    Ok(config_home + "/file-explorer-tui")
+}
+
+/// Returns the shell of the user set via $SHELL, falling back to the system shell
+pub fn get_shell() -> String {
+    let fallback_shell = if cfg!(windows) { "cmd" } else { "/bin/sh" };
+    env::var("SHELL")
+        .ok()
+        .filter(|shell| !shell.trim().is_empty())
+        .unwrap_or_else(|| String::from(fallback_shell))
+}
+
+/// Returns $HOME, falling back to the current directory and then to the root directory
+pub fn get_default_start_directory() -> String {
+    get_home_dir()
+        .ok()
+        .filter(|home_dir| !home_dir.is_empty())
+        .or_else(|| {
+            env::current_dir()
+                .ok()
+                .map(|current_dir| current_dir.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| String::from("/"))
+}
+
+/// Returns the cache directory of the explorer, respecting $XDG_CACHE_HOME and falling back to
+/// the temp directory if $HOME is not set
+pub fn get_cache_dir() -> String {
+    let cache_home = env::var("XDG_CACHE_HOME")
+        .ok()
+        .filter(|cache_home| !cache_home.is_empty())
+        .or_else(|| get_home_dir().ok().map(|home_dir| home_dir + "/.cache"))
+        .unwrap_or_else(|| env::temp_dir().to_string_lossy().to_string());
+    PathBuf::from(cache_home)
+        .join("file-explorer-tui")
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Returns the state directory of the explorer, respecting $XDG_STATE_HOME. Used for data that
+/// should survive restarts but isn't configuration, like the input history.
+pub fn get_state_dir() -> Result<String, VarError> {
+    let state_home = env::var("XDG_STATE_HOME")
+        .ok()
+        .filter(|state_home| !state_home.is_empty())
+        .map(Ok)
+        .unwrap_or_else(|| get_home_dir().map(|home_dir| home_dir + "/.local/state"))?;
+    Ok(state_home + "/file-explorer-tui")
+}
+
+/// Returns the login name of the user set via $USER or $LOGNAME
+pub fn get_user_name() -> Option<String> {
+    env::var("USER")
+        .or_else(|_| env::var("LOGNAME"))
+        .ok()
+        .filter(|user_name| !user_name.is_empty())
+}
@@ -0,0 +1,102 @@
+use std::collections::VecDeque;
+
+use chrono::{DateTime, Local};
+use notify::{Event, EventKind};
+
+/// How many events are kept, older ones are dropped
+const EVENT_LOG_CAPACITY: usize = 500;
+
+pub struct EventLogEntry {
+    pub received_at: DateTime<Local>,
+    pub kind: EventKind,
+    pub paths: Vec<String>,
+}
+
+impl EventLogEntry {
+    /// e.g. "12:03:45.123  Modify(Name(Both))  /tmp/a -> /tmp/b"
+    pub fn format(&self) -> String {
+        format!(
+            "{}  {:?}  {}",
+            self.received_at.format("%H:%M:%S%.3f"),
+            self.kind,
+            self.paths.join(" -> ")
+        )
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EventLogFilter {
+    All,
+    Create,
+    Modify,
+    Remove,
+    Access,
+    Other,
+}
+
+impl EventLogFilter {
+    pub fn next(self) -> Self {
+        match self {
+            EventLogFilter::All => EventLogFilter::Create,
+            EventLogFilter::Create => EventLogFilter::Modify,
+            EventLogFilter::Modify => EventLogFilter::Remove,
+            EventLogFilter::Remove => EventLogFilter::Access,
+            EventLogFilter::Access => EventLogFilter::Other,
+            EventLogFilter::Other => EventLogFilter::All,
+        }
+    }
+
+    pub fn get_display_name(self) -> &'static str {
+        match self {
+            EventLogFilter::All => "all",
+            EventLogFilter::Create => "create",
+            EventLogFilter::Modify => "modify",
+            EventLogFilter::Remove => "remove",
+            EventLogFilter::Access => "access",
+            EventLogFilter::Other => "other",
+        }
+    }
+
+    fn matches(self, kind: &EventKind) -> bool {
+        match self {
+            EventLogFilter::All => true,
+            EventLogFilter::Create => kind.is_create(),
+            EventLogFilter::Modify => kind.is_modify(),
+            EventLogFilter::Remove => kind.is_remove(),
+            EventLogFilter::Access => kind.is_access(),
+            EventLogFilter::Other => matches!(kind, EventKind::Any | EventKind::Other),
+        }
+    }
+}
+
+/// The most recent raw watcher events, for seeing what tools writing into watched directories
+/// actually do
+#[derive(Default)]
+pub struct EventLog {
+    entries: VecDeque<EventLogEntry>,
+}
+
+impl EventLog {
+    pub fn push(&mut self, event: &Event) {
+        if self.entries.len() == EVENT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(EventLogEntry {
+            received_at: Local::now(),
+            kind: event.kind,
+            paths: event
+                .paths
+                .iter()
+                .map(|path| path.to_string_lossy().to_string())
+                .collect(),
+        });
+    }
+
+    /// Returns the entries matching the filter, oldest first
+    pub fn get_filtered_entries(&self, filter: EventLogFilter) -> Vec<&EventLogEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| filter.matches(&entry.kind))
+            .collect()
+    }
+}
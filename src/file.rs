@@ -1,18 +1,34 @@
 use std::{
     cmp::Ordering,
+    collections::HashSet,
     fmt::Display,
-    fs::{self, create_dir},
-    io::Error,
+    fs::{self, create_dir_all, DirEntry, Metadata},
+    io::ErrorKind,
     path::Path,
+    time::SystemTime,
 };
 
+use clap::ValueEnum;
 use ratatui::text::Text;
 
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum SortMode {
+    Name,
+    /// largest first
+    Size,
+    /// most recently modified first
+    Modified,
+}
+
 #[derive(Clone)]
 pub struct File {
     pub display_name: String,
     pub full_path: String,
     pub is_dir: bool,
+    /// Read once when the file is listed and again when the watcher reports a change, so drawing
+    /// and sorting never stat
+    pub size: u64,
+    pub modified: Option<SystemTime>,
 }
 
 impl Ord for File {
@@ -73,50 +89,72 @@ impl From<File> for Text<'_> {
     }
 }
 
-pub fn get_files_for_dir(dir: &String, hidden_files: bool) -> Result<Vec<File>, Error> {
-    let read_dir_result = fs::read_dir(dir);
-
-    match read_dir_result {
-        Ok(read_dir_result) => {
-            let files: Vec<File> = read_dir_result
-                .into_iter()
-                .map(|file| {
-                    let dir_entry = file.expect("can unwrap file");
-                    let full_path = dir_entry.path().to_string_lossy().to_string();
-                    let splitted: Vec<&str> = full_path.split("/").collect();
-                    let (last, _) = splitted
-                        .split_last()
-                        .expect("Should be able to split to get relative path");
-
-                    let is_dir = is_path_directory(&full_path);
-
-                    let display_name = if is_dir {
-                        last.to_string() + "/"
-                    } else {
-                        last.to_string()
-                    };
-
-                    File {
-                        display_name,
-                        full_path,
-                        is_dir,
-                    }
-                })
-                .collect();
-
-            if hidden_files {
-                Ok(files)
-            } else {
-                let filtered_files = files
-                    .iter()
-                    .filter(|file| !file.display_name.starts_with("."))
-                    .cloned()
-                    .collect();
-                Ok(filtered_files)
+pub fn get_file_for_path(full_path: String) -> File {
+    let metadata = fs::metadata(&full_path).ok();
+    get_file_for_path_and_metadata(full_path, metadata)
+}
+
+/// Like [`get_file_for_path`], but reads the metadata through the directory entry. Only symlinks
+/// are followed to get the metadata of their target.
+pub fn get_file_for_dir_entry(dir_entry: &DirEntry) -> File {
+    let full_path = dir_entry.path().to_string_lossy().to_string();
+    let metadata = match dir_entry.file_type() {
+        Ok(file_type) if !file_type.is_symlink() => dir_entry.metadata().ok(),
+        _ => fs::metadata(&full_path).ok(),
+    };
+    get_file_for_path_and_metadata(full_path, metadata)
+}
+
+fn get_file_for_path_and_metadata(full_path: String, metadata: Option<Metadata>) -> File {
+    let is_dir = metadata.as_ref().is_some_and(|metadata| metadata.is_dir());
+    let splitted: Vec<&str> = full_path.split("/").collect();
+    let (last, _) = splitted
+        .split_last()
+        .expect("Should be able to split to get relative path");
+
+    let display_name = if is_dir {
+        last.to_string() + "/"
+    } else {
+        last.to_string()
+    };
+
+    File {
+        display_name,
+        full_path,
+        is_dir,
+        size: metadata
+            .as_ref()
+            .map(|metadata| metadata.len())
+            .unwrap_or(0),
+        modified: metadata.and_then(|metadata| metadata.modified().ok()),
+    }
+}
+
+/// Parses a list of paths separated by NUL bytes, or by newlines if there are no NUL bytes, into
+/// files. Relative paths are resolved against the current directory.
+/// Returned are the files of all existing paths and the count of paths that don't exist.
+pub fn get_files_for_path_list(path_list: &str) -> (Vec<File>, usize) {
+    let separator = if path_list.contains('\0') { '\0' } else { '\n' };
+
+    let mut files: Vec<File> = vec![];
+    let mut seen_paths: HashSet<String> = HashSet::new();
+    let mut missing_count = 0;
+    for path in path_list.split(separator) {
+        let path = path.trim_end_matches('\r');
+        if path.is_empty() {
+            continue;
+        }
+        match fs::canonicalize(path) {
+            Ok(canonical_path) => {
+                let full_path = canonical_path.to_string_lossy().to_string();
+                if seen_paths.insert(full_path.clone()) {
+                    files.push(get_file_for_path(full_path));
+                }
             }
+            Err(_) => missing_count += 1,
         }
-        Err(error) => Err(error),
     }
+    (files, missing_count)
 }
 
 // TODO: Write unit tests for this function
@@ -139,13 +177,48 @@ pub fn is_path_directory(path: &String) -> bool {
     Path::new(path).is_dir()
 }
 
-pub fn sort_file_paths_dirs_first_then_files(files: &[File]) -> Vec<File> {
-    let dirs: Vec<File> = files.iter().filter(|file| file.is_dir).cloned().collect();
-    let files: Vec<&File> = files.iter().filter(|file| !file.is_dir).collect();
+/// Formats a size in bytes with binary units, e.g. "1.5 KiB"
+pub fn format_file_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit_index = 0;
+    while value >= 1024.0 && unit_index < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit_index += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit_index])
+}
+
+/// Applies changes of the listed directory: the files with one of the changed paths are replaced
+/// by the new files, changed paths without a new file were removed. The files are sorted once
+/// after all changes are applied.
+pub fn apply_file_changes(
+    files: &mut Vec<File>,
+    changed_paths: &HashSet<String>,
+    new_files: Vec<File>,
+    sort_mode: SortMode,
+) {
+    files.retain(|file| !changed_paths.contains(&file.full_path));
+    files.extend(new_files);
+    sort_file_paths_dirs_first_then_files(files, sort_mode);
+}
 
-    let mut everything_together: Vec<File> = dirs.iter().chain(files).cloned().collect();
-    everything_together.sort();
-    everything_together
+/// Sorts directories before files, both sorted by the given sort mode. Entries that are equal
+/// regarding the sort mode are sorted by name. Uses the metadata read when the files were listed.
+pub fn sort_file_paths_dirs_first_then_files(files: &mut [File], sort_mode: SortMode) {
+    files.sort_by(|a, b| {
+        (!a.is_dir)
+            .cmp(&!b.is_dir)
+            .then_with(|| match sort_mode {
+                SortMode::Name => Ordering::Equal,
+                SortMode::Size => b.size.cmp(&a.size),
+                SortMode::Modified => b.modified.cmp(&a.modified),
+            })
+            .then_with(|| a.cmp(b))
+    });
 }
 
 /// Deletes the given file. If its just a file, it will be deleted. If its a directory, the
@@ -188,20 +261,29 @@ pub fn toggle_selected_file(selected_files: &Vec<File>, selected_file: &File) ->
     }
 }
 
-pub fn create_file(full_path: &String) -> Result<String, String> {
+/// Creates the file, or the directory if the path ends with a slash, and every missing parent
+/// directory. Returns false if it already existed, an existing file is never truncated.
+pub fn create_file(full_path: &str) -> Result<bool, String> {
     let is_dir = full_path.ends_with("/");
 
     if is_dir {
-        let result = create_dir(full_path);
-        match result {
-            Ok(()) => Ok(format!("Successfully created directory: {}", &full_path)),
-            Err(error) => Err(error.to_string()),
-        }
-    } else {
-        let result = fs::File::create(full_path);
-        match result {
-            Ok(_file) => Ok(format!("Successfully created file: {}", &full_path)),
-            Err(error) => Err(error.to_string()),
+        if Path::new(full_path).is_dir() {
+            return Ok(false);
         }
+        create_dir_all(full_path).map_err(|error| error.to_string())?;
+        return Ok(true);
+    }
+
+    if let Some(parent) = Path::new(full_path).parent() {
+        create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(full_path);
+    match result {
+        Ok(_file) => Ok(true),
+        Err(error) if error.kind() == ErrorKind::AlreadyExists => Ok(false),
+        Err(error) => Err(error.to_string()),
     }
 }
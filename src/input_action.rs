@@ -3,47 +3,202 @@ pub enum InputAction {
     None,
     DeleteFile,
     CreateFile,
+    CreateFileFromTemplate,
     RenameFile,
+    BulkRenameFind,
+    BulkRenameReplace,
+    BulkRenameConfirm,
+    ShellCommand,
 }
 
-use std::{fs::rename, io::Error};
+impl InputAction {
+    /// Returns the name under which the inputs of this prompt are kept in the input history.
+    /// None for prompts without free text input, like confirmations.
+    pub fn get_history_name(&self) -> Option<&'static str> {
+        match self {
+            InputAction::CreateFile => Some("create"),
+            InputAction::CreateFileFromTemplate => Some("create_from_template"),
+            InputAction::RenameFile => Some("rename"),
+            InputAction::BulkRenameFind => Some("bulk_rename_find"),
+            InputAction::BulkRenameReplace => Some("bulk_rename_replace"),
+            InputAction::ShellCommand => Some("shell_command"),
+            InputAction::None | InputAction::DeleteFile | InputAction::BulkRenameConfirm => None,
+        }
+    }
+}
+
+use std::{fs::rename, io::Error, path::Path, process::ExitStatus, thread};
+
+use regex::Regex;
 
 use crate::{
+    app_event::AppEvent,
+    brace_expansion::expand_input_names,
+    bulk_rename::{apply_bulk_rename, compute_bulk_rename_preview},
+    cmd::{expand_command_placeholders, run_shell_command_with_output},
     file::create_file,
-    mpsc_utils::send_message_or_panic,
-    utils::{
-        delete_currently_selected_file, delete_selected_files, refresh_files_for_working_directory,
+    message::MessageLevel,
+    mpsc_utils::{
+        send_error_message_or_panic, send_message_or_panic, send_message_with_level_or_panic,
+        send_success_message_or_panic, send_warning_message_or_panic,
     },
-    widget::{
-        get_selected_item_from_list_state, reset_current_message_and_input, reset_input, Window,
+    path_completion::resolve_input_path,
+    templates::create_file_from_template,
+    utils::{
+        delete_prompted_file, delete_selected_files, refresh_files_and_select_path,
+        refresh_files_for_working_directory, take_prompted_file,
     },
+    widget::{reset_current_message_and_input, reset_input, Window},
     AppState,
 };
 
+/// Creates every name of the input, see `expand_input_names`. The first created (or else
+/// existing) entry is selected.
 pub fn handle_create_file(app_state: &mut AppState) {
-    let full_path = app_state.working_directory.clone() + "/" + &app_state.user_input;
-    let result = create_file(&full_path);
-    match result {
-        Ok(msg) => {
-            send_message_or_panic(&mut app_state.sender_for_ui_message, msg);
-        }
+    let names = match expand_input_names(app_state.user_input.as_str()) {
+        Ok(names) => names,
         Err(error) => {
-            send_message_or_panic(
-                &mut app_state.sender_for_ui_message,
+            send_error_message_or_panic(
+                &mut app_state.app_event_sender,
                 format!("Failed to create file/dir: {}", error),
             );
+            reset_input(app_state);
+            return;
         }
+    };
+    if names.is_empty() {
+        send_warning_message_or_panic(
+            &mut app_state.app_event_sender,
+            "Nothing to create, no name entered".to_string(),
+        );
+        reset_input(app_state);
+        return;
+    }
+
+    let mut created_names = vec![];
+    let mut existing_names = vec![];
+    let mut failed_names = vec![];
+    let mut path_to_select = None;
+    let mut existing_path_to_select = None;
+    for name in &names {
+        let full_path = resolve_input_path(&app_state.working_directory, name);
+        match create_file(&full_path) {
+            Ok(true) => {
+                created_names.push(name.as_str());
+                path_to_select.get_or_insert(full_path);
+            }
+            Ok(false) => {
+                existing_names.push(name.as_str());
+                existing_path_to_select.get_or_insert(full_path);
+            }
+            Err(error) => failed_names.push(format!("{} ({})", name, error)),
+        }
+    }
+
+    let (level, message) = if let [name] = names.as_slice() {
+        match (created_names.is_empty(), existing_names.is_empty()) {
+            (false, _) => (
+                MessageLevel::Success,
+                format!("Successfully created: {}", name),
+            ),
+            (true, false) => (MessageLevel::Warning, format!("Already exists: {}", name)),
+            (true, true) => (
+                MessageLevel::Error,
+                format!("Failed to create file/dir: {}", failed_names.join(", ")),
+            ),
+        }
+    } else {
+        let mut parts = vec![];
+        if !created_names.is_empty() {
+            parts.push(format!("Created: {}", created_names.join(", ")));
+        }
+        if !existing_names.is_empty() {
+            parts.push(format!("Already existed: {}", existing_names.join(", ")));
+        }
+        if !failed_names.is_empty() {
+            parts.push(format!("Failed: {}", failed_names.join(", ")));
+        }
+        let level = if !failed_names.is_empty() {
+            MessageLevel::Error
+        } else if !existing_names.is_empty() {
+            MessageLevel::Warning
+        } else {
+            MessageLevel::Success
+        };
+        (level, parts.join("; "))
+    };
+    send_message_with_level_or_panic(&mut app_state.app_event_sender, level, message);
+
+    match path_to_select.or(existing_path_to_select) {
+        Some(path) => {
+            let entry_path = get_working_directory_entry_path(&app_state.working_directory, &path);
+            refresh_files_and_select_path(app_state, entry_path);
+        }
+        None => refresh_files_for_working_directory(app_state),
     }
-    refresh_files_for_working_directory(app_state);
     reset_input(app_state);
 }
 
+/// Creates the file named in the input from the template chosen in the template popup
+pub fn handle_create_file_from_template(app_state: &mut AppState) {
+    let Some(template) = app_state.chosen_template.take() else {
+        reset_input(app_state);
+        return;
+    };
+    if app_state.user_input.as_str().trim().is_empty() {
+        send_warning_message_or_panic(
+            &mut app_state.app_event_sender,
+            "Nothing to create, no name entered".to_string(),
+        );
+        reset_input(app_state);
+        return;
+    }
+
+    let full_path = resolve_input_path(&app_state.working_directory, app_state.user_input.as_str());
+    match create_file_from_template(&template, &full_path) {
+        Ok(()) => {
+            send_success_message_or_panic(
+                &mut app_state.app_event_sender,
+                format!(
+                    "Successfully created {} from template {}",
+                    full_path, template.name
+                ),
+            );
+            let entry_path =
+                get_working_directory_entry_path(&app_state.working_directory, &full_path);
+            refresh_files_and_select_path(app_state, entry_path);
+        }
+        Err(error) => {
+            send_error_message_or_panic(
+                &mut app_state.app_event_sender,
+                format!("Failed to create file from template: {}", error),
+            );
+            refresh_files_for_working_directory(app_state);
+        }
+    }
+    reset_input(app_state);
+}
+
+/// Returns the entry of the working directory that contains the path, e.g. `<wd>/a` for
+/// `<wd>/a/b/c.rs`. Directories are listed without the trailing slash.
+fn get_working_directory_entry_path(working_directory: &str, path: &str) -> String {
+    let working_directory = working_directory.trim_end_matches('/');
+    let path = path.trim_end_matches('/');
+    let relative_path = path
+        .strip_prefix(working_directory)
+        .and_then(|relative_path| relative_path.strip_prefix('/'));
+    match relative_path.and_then(|relative_path| relative_path.split('/').next()) {
+        Some(entry_name) => format!("{}/{}", working_directory, entry_name),
+        None => path.to_string(),
+    }
+}
+
 pub fn handle_delete_file(app_state: &mut AppState) {
-    let user_input = &app_state.user_input;
+    let user_input = app_state.user_input.as_str();
     let is_confirmed = user_input == "y" || user_input == "yes";
     if is_confirmed {
         match app_state.current_window {
-            Window::Files => delete_currently_selected_file(app_state),
+            Window::Files => delete_prompted_file(app_state),
             Window::SelectedFiles => delete_selected_files(app_state),
         }
     } else {
@@ -51,11 +206,222 @@ pub fn handle_delete_file(app_state: &mut AppState) {
     }
 }
 
-pub fn handle_rename_file(app_state: &mut AppState) -> Result<(), Error> {
-    let file = get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files);
-    let new_file_name = &app_state.user_input;
-    let result = rename(&file.full_path, new_file_name);
-    refresh_files_for_working_directory(app_state);
+pub fn handle_rename_file(app_state: &mut AppState) {
+    let Some(file) = take_prompted_file(app_state) else {
+        return;
+    };
+    let new_path = resolve_input_path(&app_state.working_directory, app_state.user_input.as_str());
+    let result = rename(&file.full_path, &new_path);
+    match result {
+        Ok(()) => refresh_files_and_select_path(app_state, new_path),
+        Err(_) => refresh_files_for_working_directory(app_state),
+    }
     reset_current_message_and_input(app_state);
-    result
+    match result {
+        Ok(()) => send_success_message_or_panic(
+            &mut app_state.app_event_sender,
+            "Successfully renamed file!".to_string(),
+        ),
+        Err(error) => send_error_message_or_panic(
+            &mut app_state.app_event_sender,
+            format!("Failed to rename file: {}", error),
+        ),
+    }
+}
+
+pub fn handle_bulk_rename_find(app_state: &mut AppState) {
+    if let Err(error) = Regex::new(app_state.user_input.as_str()) {
+        send_error_message_or_panic(
+            &mut app_state.app_event_sender,
+            format!("Invalid regex: {}", error),
+        );
+        return;
+    }
+
+    app_state.bulk_rename_find = app_state.user_input.as_str().to_string();
+    app_state.user_input.clear();
+    app_state.input_action = InputAction::BulkRenameReplace;
+    send_message_or_panic(
+        &mut app_state.app_event_sender,
+        "Enter the replacement ($1 for capture groups, {n}, {ext}, {mtime}). Esc to abort"
+            .to_string(),
+    );
+}
+
+pub fn handle_bulk_rename_replace(app_state: &mut AppState) {
+    let preview = compute_bulk_rename_preview(
+        &app_state.selected_files,
+        &app_state.bulk_rename_find,
+        app_state.user_input.as_str(),
+    );
+    match preview {
+        Ok(entries) => {
+            let conflict_count = entries.iter().filter(|entry| entry.has_conflict).count();
+            let rename_count = entries.iter().filter(|entry| !entry.is_unchanged()).count();
+            if conflict_count != 0 {
+                send_warning_message_or_panic(
+                    &mut app_state.app_event_sender,
+                    format!(
+                        "Cannot rename: {} conflict(s). Adjust the replacement or Esc to abort",
+                        conflict_count
+                    ),
+                );
+            } else if rename_count == 0 {
+                send_warning_message_or_panic(
+                    &mut app_state.app_event_sender,
+                    "Nothing to rename. Adjust the replacement or Esc to abort".to_string(),
+                );
+            } else {
+                app_state.bulk_rename_replace = app_state.user_input.as_str().to_string();
+                app_state.user_input.clear();
+                app_state.input_action = InputAction::BulkRenameConfirm;
+                send_message_or_panic(
+                    &mut app_state.app_event_sender,
+                    format!(
+                        "Please confirm renaming of {} file(s) with y/yes. Esc to abort",
+                        rename_count
+                    ),
+                );
+            }
+        }
+        Err(error) => {
+            send_error_message_or_panic(
+                &mut app_state.app_event_sender,
+                format!("Invalid regex: {}", error),
+            );
+        }
+    }
+}
+
+pub fn handle_bulk_rename_confirm(app_state: &mut AppState) {
+    let user_input = app_state.user_input.as_str();
+    let is_confirmed = user_input == "y" || user_input == "yes";
+    if !is_confirmed {
+        reset_current_message_and_input(app_state);
+        return;
+    }
+
+    // compute again, something might have changed on disk in the meantime
+    let preview = compute_bulk_rename_preview(
+        &app_state.selected_files,
+        &app_state.bulk_rename_find,
+        &app_state.bulk_rename_replace,
+    );
+    let (level, message) = match preview {
+        Ok(entries) if entries.iter().any(|entry| entry.has_conflict) => (
+            MessageLevel::Warning,
+            "Aborted bulk rename, there are conflicts now.".to_string(),
+        ),
+        Ok(entries) => {
+            let (renamed, failed) = apply_bulk_rename(&entries);
+            for entry in &renamed {
+                if let Some(selected_file) = app_state
+                    .selected_files
+                    .iter_mut()
+                    .find(|file| file.full_path == entry.old_path)
+                {
+                    let new_file_name = Path::new(&entry.new_path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    selected_file.display_name = if selected_file.is_dir {
+                        new_file_name + "/"
+                    } else {
+                        new_file_name
+                    };
+                    selected_file.full_path = entry.new_path.clone();
+                }
+            }
+            if failed.is_empty() {
+                (
+                    MessageLevel::Success,
+                    format!("Successfully renamed {} file(s).", renamed.len()),
+                )
+            } else {
+                let (first_failed_entry, first_error) = &failed[0];
+                let message = format!(
+                    "Renamed {} file(s), failed to rename {} file(s). First error for {}: {}",
+                    renamed.len(),
+                    failed.len(),
+                    first_failed_entry.old_path,
+                    first_error
+                );
+                (MessageLevel::Error, message)
+            }
+        }
+        Err(error) => (MessageLevel::Error, format!("Invalid regex: {}", error)),
+    };
+
+    send_message_with_level_or_panic(&mut app_state.app_event_sender, level, message);
+    refresh_files_for_working_directory(app_state);
+    reset_input(app_state);
+}
+
+pub fn handle_shell_command(app_state: &mut AppState) {
+    let current_file = app_state
+        .file_list_state
+        .selected()
+        .and_then(|index| app_state.files.get(index))
+        .map(|file| file.full_path.clone());
+    let selected_files: Vec<String> = app_state
+        .selected_files
+        .iter()
+        .map(|file| file.full_path.clone())
+        .collect();
+    let command_line = expand_command_placeholders(
+        app_state.user_input.as_str(),
+        current_file.as_deref(),
+        &selected_files,
+        &app_state.working_directory,
+    );
+
+    // run in the background, so long running commands don't block the UI
+    let display_command_line = app_state.user_input.as_str().to_string();
+    let working_directory = app_state.working_directory.clone();
+    let app_event_sender = app_state.app_event_sender.clone();
+    thread::spawn(move || {
+        let result = run_shell_command_with_output(&command_line, &working_directory);
+        let _ = app_event_sender.send(AppEvent::ShellCommandFinished {
+            command_line: display_command_line,
+            result,
+        });
+    });
+
+    send_message_or_panic(
+        &mut app_state.app_event_sender,
+        format!("Running: {}", app_state.user_input.as_str()),
+    );
+    reset_input(app_state);
+}
+
+pub fn handle_shell_command_finished(
+    app_state: &mut AppState,
+    command_line: String,
+    result: Result<(ExitStatus, String), Error>,
+) {
+    match result {
+        Ok((status, output)) => {
+            let level = if status.success() {
+                MessageLevel::Success
+            } else {
+                MessageLevel::Warning
+            };
+            send_message_with_level_or_panic(
+                &mut app_state.app_event_sender,
+                level,
+                format!("Command exited with {}: {}", status, command_line),
+            );
+            app_state.command_output_title = format!("Output of: {}", command_line);
+            app_state.command_output = output;
+            app_state.command_output_scroll = 0;
+            app_state.show_command_output_popup = true;
+        }
+        Err(error) => {
+            send_error_message_or_panic(
+                &mut app_state.app_event_sender,
+                format!("Failed to run command: {}", error),
+            );
+        }
+    }
+    refresh_files_for_working_directory(app_state);
 }
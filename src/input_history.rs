@@ -0,0 +1,264 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind, Write},
+    path::Path,
+    process,
+};
+
+use log::warn;
+
+use crate::{env::get_state_dir, AppState};
+
+/// Older inputs are dropped, per prompt
+const MAX_ENTRIES_PER_PROMPT: usize = 200;
+
+/// A running Ctrl-r search. The input shows the current match while searching.
+pub struct InputHistorySearch {
+    pub query: String,
+    match_index: Option<usize>,
+    input_before_search: String,
+}
+
+/// The previous inputs of every prompt, oldest first. They are persisted in the state directory
+/// as `<prompt>\t<input>` lines.
+pub struct InputHistory {
+    entries: HashMap<String, Vec<String>>,
+    file_path: Option<String>,
+    /// The index of the entry recalled with Up/Down and the input typed before recalling
+    navigation: Option<(usize, String)>,
+    pub search: Option<InputHistorySearch>,
+}
+
+impl InputHistory {
+    /// Loads the persisted history. A missing or unreadable history file results in an empty
+    /// history.
+    pub fn load() -> Self {
+        let file_path = get_state_dir().ok().map(|state_dir| state_dir + "/history");
+        let entries = file_path
+            .as_deref()
+            .map(read_history_file)
+            .unwrap_or_default();
+
+        InputHistory {
+            entries,
+            file_path,
+            navigation: None,
+            search: None,
+        }
+    }
+
+    /// Adds the submitted input as the newest entry of the prompt and persists the history. An
+    /// older identical entry is removed. Entries other instances saved in the meantime are kept.
+    pub fn add(&mut self, prompt: &str, input: &str) {
+        self.reset_navigation();
+        if input.trim().is_empty() || input.contains('\n') {
+            return;
+        }
+        if let Some(file_path) = &self.file_path {
+            self.entries = read_history_file(file_path);
+        }
+        let prompt_entries = self.entries.entry(prompt.to_string()).or_default();
+        prompt_entries.retain(|entry| entry != input);
+        prompt_entries.push(input.to_string());
+        let excess_count = prompt_entries.len().saturating_sub(MAX_ENTRIES_PER_PROMPT);
+        prompt_entries.drain(..excess_count);
+
+        self.save();
+    }
+
+    /// Forgets the Up/Down position and a running search, e.g. when the prompt is closed
+    pub fn reset_navigation(&mut self) {
+        self.navigation = None;
+        self.search = None;
+    }
+
+    fn save(&self) {
+        let Some(file_path) = &self.file_path else {
+            return;
+        };
+        let content: String = self
+            .entries
+            .iter()
+            .flat_map(|(prompt, prompt_entries)| {
+                prompt_entries
+                    .iter()
+                    .map(move |entry| format!("{}\t{}\n", prompt, entry))
+            })
+            .collect();
+
+        if let Err(error) = write_history_file(file_path, &content) {
+            warn!("Failed to write input history {}: {}", file_path, error);
+        }
+    }
+}
+
+fn read_history_file(file_path: &str) -> HashMap<String, Vec<String>> {
+    let mut entries: HashMap<String, Vec<String>> = HashMap::new();
+    match fs::read_to_string(file_path) {
+        Ok(content) => {
+            for line in content.lines() {
+                if let Some((prompt, input)) = line.split_once('\t') {
+                    entries
+                        .entry(prompt.to_string())
+                        .or_default()
+                        .push(input.to_string());
+                }
+            }
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {}
+        Err(error) => warn!("Failed to read input history {}: {}", file_path, error),
+    }
+    entries
+}
+
+/// Writes a temporary file and renames it over the history file, so a crash never leaves a
+/// truncated history behind. Like a shell history, the file is only readable by the user, since it
+/// contains shell commands.
+fn write_history_file(file_path: &str, content: &str) -> io::Result<()> {
+    if let Some(state_dir) = Path::new(file_path).parent() {
+        fs::create_dir_all(state_dir)?;
+    }
+    let temporary_path = format!("{}.tmp-{}", file_path, process::id());
+    // the mode is only applied when the file is created
+    let _ = fs::remove_file(&temporary_path);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&temporary_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&temporary_path, file_path)
+}
+
+/// Replaces the input with the previous (older) entry of the prompt's history
+pub fn recall_previous_input(app_state: &mut AppState) {
+    let Some(prompt) = app_state.input_action.get_history_name() else {
+        return;
+    };
+    let input_history = &mut app_state.input_history;
+    let Some(prompt_entries) = input_history.entries.get(prompt) else {
+        return;
+    };
+    if prompt_entries.is_empty() {
+        return;
+    }
+
+    let (index, input_before_recalling) = match input_history.navigation.take() {
+        Some((index, input_before_recalling)) => (index.saturating_sub(1), input_before_recalling),
+        None => (
+            prompt_entries.len() - 1,
+            app_state.user_input.as_str().to_string(),
+        ),
+    };
+    app_state.user_input.set_text(prompt_entries[index].clone());
+    input_history.navigation = Some((index, input_before_recalling));
+}
+
+/// Replaces the input with the next (newer) entry of the prompt's history. After the newest
+/// entry, the input typed before recalling is restored.
+pub fn recall_next_input(app_state: &mut AppState) {
+    let Some(prompt) = app_state.input_action.get_history_name() else {
+        return;
+    };
+    let input_history = &mut app_state.input_history;
+    let Some((index, input_before_recalling)) = input_history.navigation.take() else {
+        return;
+    };
+    let prompt_entries = input_history
+        .entries
+        .get(prompt)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    match prompt_entries.get(index + 1) {
+        Some(entry) => {
+            app_state.user_input.set_text(entry.clone());
+            input_history.navigation = Some((index + 1, input_before_recalling));
+        }
+        None => app_state.user_input.set_text(input_before_recalling),
+    }
+}
+
+/// Starts a reverse search through the prompt's history, or jumps to the next older match if a
+/// search is already running
+pub fn search_input_history_backwards(app_state: &mut AppState) {
+    if app_state.input_action.get_history_name().is_none() {
+        return;
+    }
+    match &app_state.input_history.search {
+        Some(search) => {
+            let before = search.match_index.unwrap_or(usize::MAX);
+            update_search_match(app_state, before);
+        }
+        None => {
+            app_state.input_history.search = Some(InputHistorySearch {
+                query: String::new(),
+                match_index: None,
+                input_before_search: app_state.user_input.as_str().to_string(),
+            });
+        }
+    }
+}
+
+/// Changes the query of the running search and searches again from the newest entry
+pub fn edit_input_history_search_query(app_state: &mut AppState, edit: impl FnOnce(&mut String)) {
+    let Some(search) = &mut app_state.input_history.search else {
+        return;
+    };
+    edit(&mut search.query);
+    update_search_match(app_state, usize::MAX);
+}
+
+/// Ends the running search. The current match stays in the input, unless the search is
+/// cancelled.
+pub fn end_input_history_search(app_state: &mut AppState, is_cancelled: bool) {
+    let Some(search) = app_state.input_history.search.take() else {
+        return;
+    };
+    if is_cancelled {
+        app_state.user_input.set_text(search.input_before_search);
+    }
+}
+
+/// Shows the newest entry before the given index that contains the query
+fn update_search_match(app_state: &mut AppState, before: usize) {
+    let Some(prompt) = app_state.input_action.get_history_name() else {
+        return;
+    };
+    let input_history = &mut app_state.input_history;
+    let Some(search) = &mut input_history.search else {
+        return;
+    };
+    let prompt_entries = input_history
+        .entries
+        .get(prompt)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let searched_entries = &prompt_entries[..before.min(prompt_entries.len())];
+    let match_index = if search.query.is_empty() {
+        None
+    } else {
+        searched_entries
+            .iter()
+            .rposition(|entry| entry.contains(&search.query))
+    };
+    // keep the previous match if there is no older one
+    if let Some(match_index) = match_index {
+        search.match_index = Some(match_index);
+        app_state
+            .user_input
+            .set_text(prompt_entries[match_index].clone());
+    } else if before == usize::MAX {
+        search.match_index = None;
+        app_state
+            .user_input
+            .set_text(search.input_before_search.clone());
+    }
+}
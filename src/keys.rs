@@ -1,15 +1,28 @@
-use std::time::Duration;
-
-use crossterm::event::{poll, read, Event, KeyCode};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 
 use crate::{
-    cmd::open_file_with_system_app,
-    file::{get_files_for_dir, sort_file_paths_dirs_first_then_files, toggle_selected_file},
-    input_action::{handle_create_file, handle_delete_file, handle_rename_file, InputAction},
-    mpsc_utils::send_message_or_panic,
+    chooser::ChooserMode,
+    cmd::{open_file_in_editor, open_shell_in_directory},
+    config::OpenerRule,
+    file::toggle_selected_file,
+    input_action::{
+        handle_bulk_rename_confirm, handle_bulk_rename_find, handle_bulk_rename_replace,
+        handle_create_file, handle_create_file_from_template, handle_delete_file,
+        handle_rename_file, handle_shell_command, InputAction,
+    },
+    input_history::{
+        edit_input_history_search_query, end_input_history_search, recall_next_input,
+        recall_previous_input, search_input_history_backwards,
+    },
+    mpsc_utils::{
+        send_error_message_or_panic, send_message_or_panic, send_warning_message_or_panic,
+    },
+    opener::{get_matching_openers, run_opener},
+    path_completion::complete_path,
+    templates::get_templates,
     utils::{
         enter_directory, get_is_in_input_mode, navigate_to_parent_directory,
-        refresh_list_state_index_of_directory,
+        refresh_files_for_working_directory, refresh_list_state_index_of_directory,
     },
     widget::{
         add_char_input, get_selected_item_from_list_state, handle_backspace,
@@ -18,82 +31,217 @@ use crate::{
     AppState,
 };
 
-pub const KEYS: [&str; 17] = [
+pub const KEYS: [&str; 32] = [
     "j to navigate down",
     "k to navigate up",
     "l to enter directory",
     "h or - to navigate to the parent directory",
     "a to create file",
+    "A to create a file from a template (files in the 'templates' directory of the config directory)",
     "o to open selected file",
+    "O to choose how to open the selected file (Open with...)",
+    "e to edit selected file in $VISUAL/$EDITOR",
     "D to delete selected file (or when in selected files window all selected files)",
     "r to rename currently selected file",
+    "R to bulk rename all selected files with a regex",
+    "! to run a shell command (%f: current file, %s: selected files, %d: directory)",
+    "S to open a shell in the current directory (exit the shell to return)",
     "q to quit the tui",
+    "Q to quit the tui without changing the directory of the shell wrapper",
     "H to toggle hidden files",
     "c to toggle cheatsheet",
+    "m to show the message history",
     "s to toggle selected files window",
+    "E to toggle the filesystem event log",
+    "F to cycle the event kind shown in the filesystem event log",
     "1 to focus 'Files' window",
     "2 to focus 'Selected files' window",
     "In 'Files': Space to add/remove file to 'Selected files' window",
     "In 'Selected files': Space to remove selected from the window",
     "Esc in input mode to abort current action",
+    "Tab/Shift-Tab when creating or renaming to complete paths, press again to cycle through the candidates",
+    "In input mode: Left/Right, Home/End (Ctrl-a/Ctrl-e), Ctrl-Left/Right (Alt-b/Alt-f) to move, Ctrl-w/Ctrl-u to delete the previous word/everything before the cursor",
+    "In input mode: Up/Down to recall previous inputs of the prompt, Ctrl-r to search them (Enter to accept, Esc to cancel)",
+    "In chooser mode: Enter on a file to choose it (--choose-files)",
+    "In chooser mode: C to choose the current file/directory or all selected files",
 ];
 
-pub fn handle_key_event(app_state: &mut AppState) -> &str {
-    let maybe_key_event =
-        poll(Duration::from_millis(100)).expect("can use poll to check if key event");
-    if maybe_key_event {
-        let event = read().expect("if poll returned true we should be able to read key event");
-        if let Event::Key(key) = event {
-            match key.code {
-                KeyCode::Char(char) => return handle_char(char, app_state),
-                KeyCode::Backspace => handle_backspace(app_state),
-                KeyCode::Esc => handle_escape(app_state),
-                KeyCode::Enter => handle_enter(app_state),
-                _ => return "ok",
-            }
+pub fn handle_key_event(key: KeyEvent, app_state: &mut AppState) -> &str {
+    // any other key ends cycling through the completion candidates
+    if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+        app_state.path_completion = None;
+    }
+
+    if get_is_in_input_mode(app_state) {
+        if handle_input_history_key(key, app_state) || handle_line_editor_key(key, app_state) {
+            return "ok";
+        }
+        // unbound combinations like Ctrl-d must not insert their letter
+        let is_control_or_alt = key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        if is_control_or_alt && matches!(key.code, KeyCode::Char(_)) {
+            return "ok";
         }
     }
+
+    match key.code {
+        KeyCode::Char(char) => return handle_char(char, app_state),
+        KeyCode::Backspace => handle_backspace(app_state),
+        KeyCode::Esc => handle_escape(app_state),
+        KeyCode::Enter => return handle_enter(app_state),
+        KeyCode::Tab => handle_tab(app_state, false),
+        KeyCode::BackTab => handle_tab(app_state, true),
+        _ => return "ok",
+    }
     "ok"
 }
 
+/// Handles the cursor movement and deletion keys of the input prompt. Returns false for keys that
+/// aren't for editing, like Enter, Esc or plain characters.
+fn handle_line_editor_key(key: KeyEvent, app_state: &mut AppState) -> bool {
+    let line_editor = &mut app_state.user_input;
+    let is_control = key.modifiers.contains(KeyModifiers::CONTROL);
+    let is_alt = key.modifiers.contains(KeyModifiers::ALT);
+    match key.code {
+        KeyCode::Left if is_control => line_editor.move_word_left(),
+        KeyCode::Right if is_control => line_editor.move_word_right(),
+        KeyCode::Left => line_editor.move_left(),
+        KeyCode::Right => line_editor.move_right(),
+        KeyCode::Home => line_editor.move_to_start(),
+        KeyCode::End => line_editor.move_to_end(),
+        KeyCode::Delete => line_editor.delete_char_at_cursor(),
+        KeyCode::Char('a') if is_control => line_editor.move_to_start(),
+        KeyCode::Char('e') if is_control => line_editor.move_to_end(),
+        KeyCode::Char('w') if is_control => line_editor.delete_word_before_cursor(),
+        KeyCode::Char('u') if is_control => line_editor.delete_to_start(),
+        KeyCode::Char('b') if is_alt => line_editor.move_word_left(),
+        KeyCode::Char('f') if is_alt => line_editor.move_word_right(),
+        KeyCode::Backspace if is_control || is_alt => line_editor.delete_word_before_cursor(),
+        _ => return false,
+    }
+    true
+}
+
+/// Handles Up/Down to recall previous inputs of the prompt and the keys of a running Ctrl-r
+/// search. Returns false for keys that should still be handled by the input prompt.
+fn handle_input_history_key(key: KeyEvent, app_state: &mut AppState) -> bool {
+    let is_control = key.modifiers.contains(KeyModifiers::CONTROL);
+    if app_state.input_history.search.is_some() {
+        match key.code {
+            KeyCode::Char('r') if is_control => search_input_history_backwards(app_state),
+            KeyCode::Char('g') if is_control => end_input_history_search(app_state, true),
+            KeyCode::Esc => end_input_history_search(app_state, true),
+            KeyCode::Enter => end_input_history_search(app_state, false),
+            KeyCode::Backspace => edit_input_history_search_query(app_state, |query| {
+                query.pop();
+            }),
+            KeyCode::Char(char) if !is_control => {
+                edit_input_history_search_query(app_state, |query| query.push(char))
+            }
+            // any other key accepts the match and is handled as usual
+            _ => {
+                end_input_history_search(app_state, false);
+                return false;
+            }
+        }
+        return true;
+    }
+
+    match key.code {
+        KeyCode::Up => recall_previous_input(app_state),
+        KeyCode::Down => recall_next_input(app_state),
+        KeyCode::Char('r') if is_control => search_input_history_backwards(app_state),
+        _ => return false,
+    }
+    true
+}
+
+fn handle_tab(app_state: &mut AppState, backwards: bool) {
+    if matches!(
+        app_state.input_action,
+        InputAction::CreateFile | InputAction::CreateFileFromTemplate | InputAction::RenameFile
+    ) {
+        complete_path(app_state, backwards);
+    }
+}
+
 fn handle_escape(app_state: &mut AppState) {
     let is_in_input_mode = get_is_in_input_mode(app_state);
     if is_in_input_mode {
         reset_current_message_and_input(app_state);
+    } else if app_state.show_open_with_popup {
+        app_state.show_open_with_popup = false;
+    } else if app_state.show_template_popup {
+        app_state.show_template_popup = false;
+    } else if app_state.show_command_output_popup {
+        app_state.show_command_output_popup = false;
+    } else if app_state.show_message_history {
+        app_state.show_message_history = false;
     } else if app_state.show_cheatsheet {
         app_state.show_cheatsheet = !app_state.show_cheatsheet;
     }
 }
 
-fn handle_enter(app_state: &mut AppState) {
+fn handle_enter(app_state: &mut AppState) -> &str {
+    if app_state.show_open_with_popup {
+        handle_open_with_popup_enter(app_state);
+        return "ok";
+    }
+
+    if app_state.show_template_popup {
+        handle_template_popup_enter(app_state);
+        return "ok";
+    }
+
+    if let Some(history_name) = app_state.input_action.get_history_name() {
+        app_state
+            .input_history
+            .add(history_name, app_state.user_input.as_str());
+    }
+
     match app_state.input_action {
         InputAction::None => {
+            if app_state.chooser_mode == ChooserMode::Files
+                && app_state.current_window == Window::Files
+            {
+                let is_file_selected = app_state
+                    .file_list_state
+                    .selected()
+                    .and_then(|index| app_state.files.get(index))
+                    .is_some_and(|file| !file.is_dir);
+                if is_file_selected {
+                    return choose_files_from_files_window(app_state);
+                }
+            }
             enter_directory(app_state);
         }
         InputAction::CreateFile => {
             handle_create_file(app_state);
         }
+        InputAction::CreateFileFromTemplate => {
+            handle_create_file_from_template(app_state);
+        }
         InputAction::DeleteFile => {
             handle_delete_file(app_state);
         }
         InputAction::RenameFile => {
-            let result = handle_rename_file(app_state);
-            match result {
-                Ok(()) => {
-                    send_message_or_panic(
-                        &mut app_state.sender_for_ui_message,
-                        "Successfully renamed file!".to_string(),
-                    );
-                }
-                Err(val) => {
-                    send_message_or_panic(
-                        &mut app_state.sender_for_ui_message,
-                        format!("Failed to rename file: {}", val),
-                    );
-                }
-            }
+            handle_rename_file(app_state);
+        }
+        InputAction::BulkRenameFind => {
+            handle_bulk_rename_find(app_state);
+        }
+        InputAction::BulkRenameReplace => {
+            handle_bulk_rename_replace(app_state);
+        }
+        InputAction::BulkRenameConfirm => {
+            handle_bulk_rename_confirm(app_state);
+        }
+        InputAction::ShellCommand => {
+            handle_shell_command(app_state);
         }
     };
+    "ok"
 }
 
 fn handle_char(char: char, app_state: &mut AppState) -> &str {
@@ -102,10 +250,32 @@ fn handle_char(char: char, app_state: &mut AppState) -> &str {
         return "ok";
     }
 
+    if app_state.show_open_with_popup {
+        handle_open_with_popup_char(char, app_state);
+        return "ok";
+    }
+
+    if app_state.show_template_popup {
+        handle_template_popup_char(char, app_state);
+        return "ok";
+    }
+
+    if app_state.show_command_output_popup {
+        handle_command_output_popup_char(char, app_state);
+        return "ok";
+    }
+
+    if app_state.show_message_history {
+        handle_message_history_char(char, app_state);
+        return "ok";
+    }
+
     match char {
         'j' => handle_j_char(app_state),
         'k' => handle_k_char(app_state),
         'q' => return handle_q_char(app_state),
+        'Q' => return handle_uppercase_q_char(app_state),
+        'C' => return handle_uppercase_c_char(app_state),
         ' ' => handle_space(app_state),
         'h' | '-' => navigate_to_parent_directory(app_state),
         'l' => enter_directory(app_state),
@@ -113,11 +283,20 @@ fn handle_char(char: char, app_state: &mut AppState) -> &str {
         '2' => handle_two_char(app_state),
         'D' => handle_uppercase_d_char(app_state),
         'a' => handle_a_char(app_state),
+        'A' => handle_uppercase_a_char(app_state),
         'o' => handle_o_char(app_state),
+        'O' => handle_uppercase_o_char(app_state),
+        'e' => handle_e_char(app_state),
         'r' => handle_r_char(app_state),
+        'R' => handle_uppercase_r_char(app_state),
         'c' => handle_c_char(app_state),
         's' => handle_s_char(app_state),
         'H' => handle_uppercase_h_char(app_state),
+        '!' => handle_exclamation_mark_char(app_state),
+        'S' => handle_uppercase_s_char(app_state),
+        'E' => handle_uppercase_e_char(app_state),
+        'F' => handle_uppercase_f_char(app_state),
+        'm' => handle_m_char(app_state),
         _ => {}
     }
     "ok"
@@ -125,34 +304,91 @@ fn handle_char(char: char, app_state: &mut AppState) -> &str {
 
 fn handle_uppercase_h_char(app_state: &mut AppState) {
     send_message_or_panic(
-        &mut app_state.sender_for_ui_message,
+        &mut app_state.app_event_sender,
         format!("Hidden files shown: {:?}", !app_state.show_hidden_files),
     );
 
     app_state.show_hidden_files = !app_state.show_hidden_files;
-    let new_files = get_files_for_dir(&app_state.working_directory, app_state.show_hidden_files)
-        .expect("Can get files in same directory when toggling hidden files");
-    app_state.files = sort_file_paths_dirs_first_then_files(&new_files);
+    refresh_files_for_working_directory(app_state);
 }
 
 fn handle_s_char(app_state: &mut AppState) {
     app_state.show_selected_files_window = !app_state.show_selected_files_window
 }
 
+fn handle_uppercase_e_char(app_state: &mut AppState) {
+    app_state.show_event_log = !app_state.show_event_log;
+}
+
+fn handle_uppercase_f_char(app_state: &mut AppState) {
+    if !app_state.show_event_log {
+        return;
+    }
+    app_state.event_log_filter = app_state.event_log_filter.next();
+}
+
+fn handle_m_char(app_state: &mut AppState) {
+    app_state.show_message_history = true;
+    // the newest message is at the bottom
+    app_state.message_history_list_state.select_last();
+}
+
+fn handle_message_history_char(char: char, app_state: &mut AppState) {
+    match char {
+        'j' => app_state.message_history_list_state.select_next(),
+        'k' => app_state.message_history_list_state.select_previous(),
+        'q' | 'm' => app_state.show_message_history = false,
+        _ => {}
+    }
+}
+
 fn handle_c_char(app_state: &mut AppState) {
     app_state.show_cheatsheet = !app_state.show_cheatsheet;
 }
 
 fn handle_r_char(app_state: &mut AppState) {
-    let file = get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files);
+    if is_blocked_by_read_only(app_state, "Renaming files") {
+        return;
+    }
+
+    let Some(file) =
+        get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files)
+    else {
+        return;
+    };
 
     send_message_or_panic(
-        &mut app_state.sender_for_ui_message,
+        &mut app_state.app_event_sender,
         "Please enter the new filename. Esc to abort".to_string(),
     );
 
     app_state.input_action = InputAction::RenameFile;
-    app_state.user_input = file.full_path.clone();
+    app_state.user_input.set_text(file.full_path.clone());
+    app_state.prompted_file = Some(file.clone());
+}
+
+fn handle_uppercase_r_char(app_state: &mut AppState) {
+    if is_blocked_by_read_only(app_state, "Renaming files") {
+        return;
+    }
+
+    if app_state.selected_files.is_empty() {
+        send_warning_message_or_panic(
+            &mut app_state.app_event_sender,
+            "No files selected. Use Space to add files to the 'Selected files' window".to_string(),
+        );
+        return;
+    }
+
+    send_message_or_panic(
+        &mut app_state.app_event_sender,
+        "Enter the regex to find in the selected file names. Esc to abort".to_string(),
+    );
+
+    app_state.input_action = InputAction::BulkRenameFind;
+    app_state.user_input.clear();
+    // the selected files might have changed since the last bulk rename
+    app_state.bulk_rename_preview = None;
 }
 
 fn handle_j_char(app_state: &mut AppState) {
@@ -188,14 +424,21 @@ fn handle_q_char(app_state: &mut AppState) -> &str {
     "ok"
 }
 
+fn handle_uppercase_q_char(app_state: &mut AppState) -> &str {
+    if app_state.input_action == InputAction::None {
+        return "quit_without_cd";
+    }
+    "ok"
+}
+
 fn handle_space(app_state: &mut AppState) {
     match app_state.current_window {
         Window::Files => {
-            let selected_file_index = app_state.file_list_state.selected();
-            let selected_file = app_state
-                .files
-                .get(selected_file_index.expect("there should be a selected file"))
-                .expect("[files_window]: the selected file should exist");
+            let Some(selected_file) =
+                get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files)
+            else {
+                return;
+            };
 
             let new_selected_files = toggle_selected_file(&app_state.selected_files, selected_file);
             app_state.selected_files = new_selected_files;
@@ -235,24 +478,32 @@ fn handle_two_char(app_state: &mut AppState) {
 }
 
 fn handle_uppercase_d_char(app_state: &mut AppState) {
+    if is_blocked_by_read_only(app_state, "Deleting files") {
+        return;
+    }
+
     match app_state.current_window {
         Window::Files => {
-            let file =
-                get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files);
+            let Some(file) =
+                get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files)
+            else {
+                return;
+            };
             app_state.input_action = InputAction::DeleteFile;
 
             send_message_or_panic(
-                &mut app_state.sender_for_ui_message,
+                &mut app_state.app_event_sender,
                 format!(
                     "Please confirm deletion of file {} with y/yes. Esc to abort",
                     file.full_path
                 ),
             );
+            app_state.prompted_file = Some(file.clone());
         }
         Window::SelectedFiles => {
             app_state.input_action = InputAction::DeleteFile;
             send_message_or_panic(
-                &mut app_state.sender_for_ui_message,
+                &mut app_state.app_event_sender,
                 "Please confirm deletion of all selected files with y/yes. Esc to abort"
                     .to_string(),
             )
@@ -261,22 +512,270 @@ fn handle_uppercase_d_char(app_state: &mut AppState) {
 }
 
 fn handle_a_char(app_state: &mut AppState) {
+    if is_blocked_by_read_only(app_state, "Creating files") {
+        return;
+    }
+
     app_state.input_action = InputAction::CreateFile;
     send_message_or_panic(
-        &mut app_state.sender_for_ui_message,
-        "Enter the name for new filename: (Tip: use a trailing slash to create a directory)".into(),
+        &mut app_state.app_event_sender,
+        "Enter the name for new filename: (Tip: trailing slash for a directory, several names or {a,b}/{1..3} braces create several)".into(),
+    );
+}
+
+fn handle_uppercase_a_char(app_state: &mut AppState) {
+    if is_blocked_by_read_only(app_state, "Creating files") {
+        return;
+    }
+
+    let Some(templates_dir) = &app_state.templates_dir else {
+        send_warning_message_or_panic(
+            &mut app_state.app_event_sender,
+            "No templates directory, the config directory is unknown".into(),
+        );
+        return;
+    };
+    match get_templates(templates_dir) {
+        Ok(templates) if templates.is_empty() => send_warning_message_or_panic(
+            &mut app_state.app_event_sender,
+            format!("No templates found, add files to {}", templates_dir),
+        ),
+        Ok(templates) => {
+            app_state.templates = templates;
+            app_state.template_list_state.select(Some(0));
+            app_state.show_template_popup = true;
+        }
+        Err(error) => send_error_message_or_panic(&mut app_state.app_event_sender, error),
+    }
+}
+
+fn handle_template_popup_char(char: char, app_state: &mut AppState) {
+    match char {
+        'j' => app_state.template_list_state.select_next(),
+        'k' => app_state.template_list_state.select_previous(),
+        'q' => app_state.show_template_popup = false,
+        _ => {}
+    }
+}
+
+fn handle_template_popup_enter(app_state: &mut AppState) {
+    app_state.show_template_popup = false;
+    let Some(template) = app_state
+        .template_list_state
+        .selected()
+        .and_then(|index| app_state.templates.get(index))
+        .cloned()
+    else {
+        return;
+    };
+
+    send_message_or_panic(
+        &mut app_state.app_event_sender,
+        format!(
+            "Enter the name for the new file from template {}. Esc to abort",
+            template.name
+        ),
     );
+    app_state.input_action = InputAction::CreateFileFromTemplate;
+    app_state.user_input.set_text(template.name.clone());
+    app_state.chosen_template = Some(template);
 }
 
 fn handle_o_char(app_state: &mut AppState) {
-    let selected_file =
+    let Some(selected_file) =
+        get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files)
+    else {
+        return;
+    };
+    let full_path_of_selected_file = selected_file.full_path.clone();
+    let openers = get_matching_openers(&app_state.config, &full_path_of_selected_file);
+    // there is always at least the system default opener
+    run_opener_for_file(app_state, &openers[0], &full_path_of_selected_file);
+}
+
+fn handle_uppercase_o_char(app_state: &mut AppState) {
+    let Some(selected_file) =
+        get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files)
+    else {
+        return;
+    };
+    app_state.open_with_openers = get_matching_openers(&app_state.config, &selected_file.full_path);
+    app_state.open_with_list_state.select(Some(0));
+    app_state.show_open_with_popup = true;
+}
+
+fn handle_open_with_popup_char(char: char, app_state: &mut AppState) {
+    match char {
+        'j' => app_state.open_with_list_state.select_next(),
+        'k' => app_state.open_with_list_state.select_previous(),
+        'q' => app_state.show_open_with_popup = false,
+        _ => {}
+    }
+}
+
+fn handle_open_with_popup_enter(app_state: &mut AppState) {
+    app_state.show_open_with_popup = false;
+    let maybe_opener = app_state
+        .open_with_list_state
+        .selected()
+        .and_then(|index| app_state.open_with_openers.get(index))
+        .cloned();
+    // the directory might have become empty while the popup was open
+    let maybe_selected_file =
         get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files);
-    let full_path_of_selected_file = &selected_file.full_path;
-    let open_file_with_system_app_result = open_file_with_system_app(full_path_of_selected_file);
-    match open_file_with_system_app_result {
+    if let (Some(opener), Some(selected_file)) = (maybe_opener, maybe_selected_file) {
+        let full_path_of_selected_file = selected_file.full_path.clone();
+        run_opener_for_file(app_state, &opener, &full_path_of_selected_file);
+    }
+}
+
+fn run_opener_for_file(app_state: &mut AppState, opener: &OpenerRule, file_path: &str) {
+    // a foreground opener is usually an editor or another tool that can change the file
+    if opener.foreground && is_blocked_by_read_only(app_state, "Running foreground openers") {
+        return;
+    }
+    if let Err(error) = run_opener(opener, file_path) {
+        send_error_message_or_panic(&mut app_state.app_event_sender, error);
+    }
+    if opener.foreground {
+        app_state.needs_full_redraw = true;
+        refresh_files_for_working_directory(app_state);
+    }
+}
+fn handle_e_char(app_state: &mut AppState) {
+    let Some(selected_file) =
+        get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files)
+    else {
+        return;
+    };
+    let full_path_of_selected_file = selected_file.full_path.clone();
+    if is_blocked_by_read_only(app_state, "Editing files") {
+        return;
+    }
+    let open_file_in_editor_result = open_file_in_editor(&full_path_of_selected_file);
+    app_state.needs_full_redraw = true;
+    match open_file_in_editor_result {
+        Ok(status) if !status.success() => {
+            send_warning_message_or_panic(
+                &mut app_state.app_event_sender,
+                format!("Editor exited with {}", status),
+            );
+        }
         Ok(_) => {}
         Err(error) => {
-            send_message_or_panic(&mut app_state.sender_for_ui_message, error.to_string());
+            send_error_message_or_panic(
+                &mut app_state.app_event_sender,
+                format!("Failed to open editor: {}", error),
+            );
         }
     }
+    refresh_files_for_working_directory(app_state);
+}
+
+fn handle_exclamation_mark_char(app_state: &mut AppState) {
+    if is_blocked_by_read_only(app_state, "Running shell commands") {
+        return;
+    }
+
+    app_state.input_action = InputAction::ShellCommand;
+    send_message_or_panic(
+        &mut app_state.app_event_sender,
+        "Enter a shell command (%f: current file, %s: selected files, %d: directory). Esc to abort"
+            .to_string(),
+    );
+}
+
+fn handle_command_output_popup_char(char: char, app_state: &mut AppState) {
+    match char {
+        'j' => app_state.command_output_scroll = app_state.command_output_scroll.saturating_add(1),
+        'k' => app_state.command_output_scroll = app_state.command_output_scroll.saturating_sub(1),
+        'g' => app_state.command_output_scroll = 0,
+        'q' => app_state.show_command_output_popup = false,
+        _ => {}
+    }
+}
+
+fn handle_uppercase_s_char(app_state: &mut AppState) {
+    if is_blocked_by_read_only(app_state, "Opening a shell") {
+        return;
+    }
+    let open_shell_result = open_shell_in_directory(&app_state.working_directory);
+    app_state.needs_full_redraw = true;
+    if let Err(error) = open_shell_result {
+        send_error_message_or_panic(
+            &mut app_state.app_event_sender,
+            format!("Failed to open shell: {}", error),
+        );
+    }
+    refresh_files_for_working_directory(app_state);
+}
+
+fn handle_uppercase_c_char(app_state: &mut AppState) -> &str {
+    match (app_state.chooser_mode, &app_state.current_window) {
+        (ChooserMode::None, _) => "ok",
+        (ChooserMode::Files, Window::Files) => {
+            let is_file_selected = app_state
+                .file_list_state
+                .selected()
+                .and_then(|index| app_state.files.get(index))
+                .is_some_and(|file| !file.is_dir);
+            if is_file_selected {
+                choose_files_from_files_window(app_state)
+            } else {
+                send_warning_message_or_panic(
+                    &mut app_state.app_event_sender,
+                    "Please select a file to choose".to_string(),
+                );
+                "ok"
+            }
+        }
+        (ChooserMode::Directory, Window::Files) => {
+            app_state.chosen_paths = vec![app_state.working_directory.clone()];
+            "choose"
+        }
+        (chooser_mode, Window::SelectedFiles) => {
+            let choose_directories = chooser_mode == ChooserMode::Directory;
+            let chosen_paths: Vec<String> = app_state
+                .selected_files
+                .iter()
+                .filter(|file| file.is_dir == choose_directories)
+                .map(|file| file.full_path.clone())
+                .collect();
+            if chosen_paths.is_empty() {
+                let kind = if choose_directories {
+                    "directories"
+                } else {
+                    "files"
+                };
+                send_warning_message_or_panic(
+                    &mut app_state.app_event_sender,
+                    format!("There are no {} in the 'Selected files' window", kind),
+                );
+                return "ok";
+            }
+            app_state.chosen_paths = chosen_paths;
+            "choose"
+        }
+    }
+}
+
+fn choose_files_from_files_window(app_state: &mut AppState) -> &str {
+    let Some(selected_file) =
+        get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files)
+    else {
+        return "ok";
+    };
+    app_state.chosen_paths = vec![selected_file.full_path.clone()];
+    "choose"
+}
+
+/// Returns true and tells the user if the explorer runs in read-only mode
+fn is_blocked_by_read_only(app_state: &mut AppState, action: &str) -> bool {
+    if app_state.read_only {
+        send_warning_message_or_panic(
+            &mut app_state.app_event_sender,
+            format!("{} is disabled in read-only mode", action),
+        );
+    }
+    app_state.read_only
 }
@@ -0,0 +1,161 @@
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// The text of the input prompt with a cursor. The cursor is a byte index that is always on a
+/// char boundary. Words are runs of alphanumeric characters, so e.g. Ctrl-w deletes a single
+/// path component.
+#[derive(Default)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+    /// First visible display column, the input is scrolled horizontally to keep the cursor visible
+    scroll_offset: usize,
+}
+
+impl LineEditor {
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Replaces the text and moves the cursor to its end
+    pub fn set_text(&mut self, text: String) {
+        self.cursor = text.len();
+        self.text = text;
+        self.scroll_offset = 0;
+    }
+
+    pub fn get_text_before_cursor(&self) -> &str {
+        &self.text[..self.cursor]
+    }
+
+    /// Replaces the text before the cursor, the cursor stays behind it
+    pub fn replace_before_cursor(&mut self, text: String) {
+        self.text.replace_range(..self.cursor, &text);
+        self.cursor = text.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.set_text(String::new());
+    }
+
+    pub fn insert_char(&mut self, char: char) {
+        self.text.insert(self.cursor, char);
+        self.cursor += char.len_utf8();
+    }
+
+    pub fn delete_char_before_cursor(&mut self) {
+        if let Some(previous_char_index) = self.get_previous_char_index() {
+            self.text.remove(previous_char_index);
+            self.cursor = previous_char_index;
+        }
+    }
+
+    pub fn delete_char_at_cursor(&mut self) {
+        if self.cursor < self.text.len() {
+            self.text.remove(self.cursor);
+        }
+    }
+
+    pub fn delete_word_before_cursor(&mut self) {
+        let word_start = self.get_previous_word_start();
+        self.text.replace_range(word_start..self.cursor, "");
+        self.cursor = word_start;
+    }
+
+    pub fn delete_to_start(&mut self) {
+        self.text.replace_range(..self.cursor, "");
+        self.cursor = 0;
+    }
+
+    pub fn move_left(&mut self) {
+        if let Some(previous_char_index) = self.get_previous_char_index() {
+            self.cursor = previous_char_index;
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        if let Some(char) = self.text[self.cursor..].chars().next() {
+            self.cursor += char.len_utf8();
+        }
+    }
+
+    pub fn move_to_start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_to_end(&mut self) {
+        self.cursor = self.text.len();
+    }
+
+    pub fn move_word_left(&mut self) {
+        self.cursor = self.get_previous_word_start();
+    }
+
+    pub fn move_word_right(&mut self) {
+        let rest = &self.text[self.cursor..];
+        let word_start = rest
+            .find(|char: char| char.is_alphanumeric())
+            .unwrap_or(rest.len());
+        let word_end = rest[word_start..]
+            .find(|char: char| !char.is_alphanumeric())
+            .map(|index| word_start + index)
+            .unwrap_or(rest.len());
+        self.cursor += word_end;
+    }
+
+    /// Returns the part of the text that fits into the given width and the display column of the
+    /// cursor within it. Scrolls horizontally if the cursor would be outside of it.
+    pub fn get_visible_text(&mut self, width: usize) -> (String, usize) {
+        let cursor_column = self.text[..self.cursor].width();
+        if cursor_column < self.scroll_offset {
+            self.scroll_offset = cursor_column;
+        } else if width != 0 && cursor_column >= self.scroll_offset + width {
+            self.scroll_offset = cursor_column + 1 - width;
+        }
+        // the offset might be in the middle of a double-width char, the visible text starts with
+        // the next char then. It is still at or before the cursor, which is on a char boundary.
+        let mut column = 0;
+        for char in self.text.chars() {
+            if column >= self.scroll_offset {
+                break;
+            }
+            column += char.width().unwrap_or(0);
+        }
+        self.scroll_offset = column;
+
+        let mut visible_text = String::new();
+        let mut column = 0;
+        for char in self.text.chars() {
+            let char_width = char.width().unwrap_or(0);
+            if column >= self.scroll_offset {
+                if column + char_width > self.scroll_offset + width {
+                    break;
+                }
+                visible_text.push(char);
+            }
+            column += char_width;
+        }
+        (visible_text, cursor_column - self.scroll_offset)
+    }
+
+    fn get_previous_char_index(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(index, _)| index)
+    }
+
+    /// Skips the separators before the cursor, then the word before them
+    fn get_previous_word_start(&self) -> usize {
+        let mut word_start = self.cursor;
+        let mut is_in_word = false;
+        for (index, char) in self.text[..self.cursor].char_indices().rev() {
+            if char.is_alphanumeric() {
+                is_in_word = true;
+            } else if is_in_word {
+                break;
+            }
+            word_start = index;
+        }
+        word_start
+    }
+}
@@ -1,10 +1,9 @@
 use flexi_logger::{FileSpec, LoggerHandle};
 
-use crate::env::get_home_dir;
+use crate::env::get_cache_dir;
 
 pub fn setup_logger_handle() -> LoggerHandle {
-    let home_dir = get_home_dir().expect("$HOME is set");
-    let log_file_directory = home_dir + "/.cache/file-explorer-tui/";
+    let log_file_directory = get_cache_dir();
 
     let default_file_spec = FileSpec::default().directory(log_file_directory);
 
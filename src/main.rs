@@ -1,28 +1,73 @@
 use log::{error, info};
-use notify::Watcher;
-use std::collections::HashMap;
-use std::path::Path;
-use std::sync::mpsc::{channel, Sender};
-
-use color_eyre::Result;
-use directory_watcher::{handle_notify_watcher_event, setup_directory_watcher};
-use input_action::InputAction;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{stdin, Read};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use app_event::{spawn_terminal_event_reader, AppEvent};
+use crossterm::event::{Event, KeyEventKind};
+
+use bulk_rename::BulkRenamePreview;
+use chooser::{write_chosen_paths, ChooserMode};
+use clap::Parser;
+use cli::Cli;
+use color_eyre::{eyre::WrapErr, Result};
+use config::{get_default_config_path, load_config, Config, OpenerRule};
+use directory_watcher::{
+    apply_pending_watcher_changes_if_due, get_time_until_pending_watcher_changes_are_due,
+    handle_notify_watcher_event, setup_directory_watcher, DirectoryWatcher,
+};
+use input_action::{handle_shell_command_finished, InputAction};
+use input_history::InputHistory;
 use keys::handle_key_event;
+use line_editor::LineEditor;
 use logger::setup_logger_handle;
-use ratatui::{widgets::ListState, DefaultTerminal};
+use message::AppStateMessage;
+use mpsc_utils::{
+    send_error_message_or_panic, send_message_or_panic, send_warning_message_or_panic,
+};
+use path_completion::PathCompletion;
+use ratatui::widgets::ListState;
+use templates::{get_templates_dir, Template};
+use tui::{init_tui, restore_tui, Tui};
+use utils::{get_is_in_input_mode, handle_directory_load_message};
 
-use env::get_home_dir;
-use file::{get_files_for_dir, sort_file_paths_dirs_first_then_files, File};
-use widget::{draw_widgets_to_frame, Window};
+use directory_cache::DirectoryCache;
+use directory_loader::{start_directory_load, DirectoryLoad};
+use env::get_default_start_directory;
+use event_log::{EventLog, EventLogFilter};
+use file::{get_files_for_path_list, File, SortMode};
+use recent_changes::RecentChanges;
+use widget::{draw_widgets_to_frame, Window, LOADING_INDICATOR_DELAY};
 
+mod app_event;
+mod brace_expansion;
+mod bulk_rename;
+mod chooser;
+mod cli;
 mod cmd;
+mod config;
+mod directory_cache;
+mod directory_loader;
 mod directory_watcher;
 mod env;
+mod event_log;
 mod file;
 mod input_action;
+mod input_history;
 mod keys;
+mod line_editor;
 mod logger;
+mod message;
+mod mounts;
 mod mpsc_utils;
+mod opener;
+mod path_completion;
+mod recent_changes;
+mod templates;
+mod tui;
 mod utils;
 mod widget;
 
@@ -41,7 +86,8 @@ struct AppState {
     selected_files: Vec<File>,
     working_directory: String,
     current_window: Window,
-    user_input: String,
+    user_input: LineEditor,
+    input_history: InputHistory,
     input_action: InputAction,
     file_list_state: ListState,
     list_state_index_of_directory: HashMap<String, usize>,
@@ -49,46 +95,167 @@ struct AppState {
     show_cheatsheet: bool,
     show_selected_files_window: bool,
     show_hidden_files: bool,
-    sender_for_ui_message: Sender<String>,
-}
-
-struct AppStateMessage {
-    // previous_messages: Vec<String>,
-    current_message: String,
+    app_event_sender: Sender<AppEvent>,
+    bulk_rename_find: String,
+    bulk_rename_replace: String,
+    bulk_rename_preview: Option<BulkRenamePreview>,
+    // set when another program used the terminal, so the next draw needs to start from scratch
+    needs_full_redraw: bool,
+    config: Config,
+    show_open_with_popup: bool,
+    open_with_openers: Vec<OpenerRule>,
+    open_with_list_state: ListState,
+    show_template_popup: bool,
+    /// `templates` next to the config file, None if there is no config directory
+    templates_dir: Option<String>,
+    templates: Vec<Template>,
+    template_list_state: ListState,
+    /// The template of the running "new from template" prompt
+    chosen_template: Option<Template>,
+    /// The file under the cursor when the delete or rename prompt was opened. The prompt acts on
+    /// it even if the cursor moves to another file meanwhile.
+    prompted_file: Option<File>,
+    show_command_output_popup: bool,
+    command_output_title: String,
+    command_output: String,
+    command_output_scroll: u16,
+    chooser_mode: ChooserMode,
+    chosen_paths: Vec<String>,
+    sort_mode: SortMode,
+    read_only: bool,
+    directory_load: Option<DirectoryLoad>,
+    directory_cache: DirectoryCache,
+    directory_watcher: DirectoryWatcher,
+    pending_watcher_paths: HashSet<PathBuf>,
+    pending_watcher_paths_since: Option<Instant>,
+    recent_changes: RecentChanges,
+    show_event_log: bool,
+    event_log: EventLog,
+    event_log_filter: EventLogFilter,
+    show_message_history: bool,
+    message_history_list_state: ListState,
+    path_completion: Option<PathCompletion>,
 }
 
 fn main() -> Result<()> {
+    // parse before setting up the terminal, so --help and errors are printed normally
+    let cli = Cli::parse();
+
+    // read before setting up the terminal. Key events are read from the controlling terminal if
+    // stdin is not a terminal
+    let path_list_for_selection = read_path_list_for_selection(&cli)?;
+
     setup_logger_handle();
     info!("file-explorer-tui is starting...");
 
     // installs error handling hook
     color_eyre::install()?;
 
-    let terminal = ratatui::init();
-    let result = run(terminal);
-    ratatui::restore();
+    let terminal = init_tui()?;
+    let result = run(terminal, &cli, path_list_for_selection);
+    restore_tui();
     info!("file-explorer-tui is stopping...");
-    result
+
+    let chosen_paths = result?;
+    if let Some(chooser_output_path) = cli.get_chooser_output_path() {
+        match chosen_paths {
+            Some(chosen_paths) => {
+                write_chosen_paths(&chosen_paths, chooser_output_path, cli.print0)?
+            }
+            None => {
+                info!("Chooser was cancelled");
+                std::process::exit(1);
+            }
+        }
+    }
+    Ok(())
 }
 
-fn run(mut terminal: DefaultTerminal) -> Result<()> {
-    // TODO: fall back to something sane
-    let initial_directory = get_home_dir().expect("$HOME is set");
+/// Runs the TUI until the user quits. Returned are the chosen paths, if the user chose any in
+/// chooser mode.
+fn run(
+    mut terminal: Tui,
+    cli: &Cli,
+    path_list_for_selection: Option<String>,
+) -> Result<Option<Vec<String>>> {
+    let (initial_directory, initial_file) = get_initial_directory_and_file(cli)?;
+
+    // a hidden start file would not be listed otherwise
+    let is_initial_file_hidden = initial_file.as_ref().is_some_and(|initial_file| {
+        Path::new(initial_file)
+            .file_name()
+            .is_some_and(|file_name| file_name.to_string_lossy().starts_with('.'))
+    });
+    let show_hidden_files = cli.hidden || is_initial_file_hidden;
+
+    // fail early if we can't read the initial directory, the listing happens in the background
+    fs::read_dir(&initial_directory)
+        .wrap_err_with(|| format!("Failed to read directory {}", initial_directory))?;
+    let (mut app_event_sender, app_event_receiver) = channel();
+
+    let mut initial_directory_load = start_directory_load(
+        initial_directory.clone(),
+        show_hidden_files,
+        true,
+        app_event_sender.clone(),
+    );
+    // if a file was given, start with the cursor on it
+    initial_directory_load.path_to_select = initial_file;
 
-    let show_hidden_files = false;
+    let config_path = cli.config.clone().or_else(get_default_config_path);
+    let templates_dir = config_path.as_deref().map(get_templates_dir);
+    let config = match config_path {
+        Some(config_path) if cli.config.is_some() && !Path::new(&config_path).exists() => {
+            let error = format!("Config file {} does not exist", config_path);
+            error!("{}", error);
+            send_error_message_or_panic(&mut app_event_sender, error);
+            Config::default()
+        }
+        Some(config_path) => load_config(&config_path).unwrap_or_else(|error| {
+            error!("{}", error);
+            send_error_message_or_panic(&mut app_event_sender, error);
+            Config::default()
+        }),
+        None => Config::default(),
+    };
 
-    let initial_files = get_files_for_dir(&initial_directory, show_hidden_files)
-        .expect("Can get files from initial dirtectory");
-    let sorted_initial_files = sort_file_paths_dirs_first_then_files(&initial_files);
+    let mut selected_files = vec![];
+    if let Some(path_list) = path_list_for_selection {
+        let (files, missing_count) = get_files_for_path_list(&path_list);
+        if missing_count == 0 {
+            send_message_or_panic(
+                &mut app_event_sender,
+                format!("Selected {} file(s)", files.len()),
+            );
+        } else {
+            send_warning_message_or_panic(
+                &mut app_event_sender,
+                format!(
+                    "Selected {} file(s), {} path(s) do not exist",
+                    files.len(),
+                    missing_count
+                ),
+            );
+        }
+        selected_files = files;
+    }
 
-    let (sender_for_ui_message, receiver_for_ui_message) = channel();
+    let directory_cache = DirectoryCache::new(config.directory_cache_size);
+    let recent_changes = RecentChanges::new(Duration::from_millis(config.highlight_changes_ms));
+    let directory_watcher = setup_directory_watcher(
+        &initial_directory,
+        cli.watcher.unwrap_or(config.watcher),
+        Duration::from_millis(cli.poll_interval.unwrap_or(config.poll_interval_ms)),
+        app_event_sender.clone(),
+    );
 
     let mut app_state = AppState {
-        files: sorted_initial_files,
-        selected_files: vec![],
+        files: vec![],
+        selected_files,
         working_directory: initial_directory.clone(),
         current_window: Window::Files,
-        user_input: String::from(""),
+        user_input: LineEditor::default(),
+        input_history: InputHistory::load(),
         input_action: InputAction::None,
         file_list_state: ListState::default(),
         selected_files_list_state: ListState::default(),
@@ -96,13 +263,44 @@ fn run(mut terminal: DefaultTerminal) -> Result<()> {
         show_cheatsheet: false,
         show_selected_files_window: true,
         show_hidden_files,
-        sender_for_ui_message,
+        app_event_sender,
+        bulk_rename_find: String::from(""),
+        bulk_rename_replace: String::from(""),
+        bulk_rename_preview: None,
+        needs_full_redraw: false,
+        config,
+        show_open_with_popup: false,
+        open_with_openers: vec![],
+        open_with_list_state: ListState::default(),
+        show_template_popup: false,
+        templates_dir,
+        templates: vec![],
+        template_list_state: ListState::default(),
+        chosen_template: None,
+        prompted_file: None,
+        show_command_output_popup: false,
+        command_output_title: String::from(""),
+        command_output: String::from(""),
+        command_output_scroll: 0,
+        chooser_mode: cli.get_chooser_mode(),
+        chosen_paths: vec![],
+        sort_mode: cli.sort,
+        read_only: cli.read_only,
+        directory_load: Some(initial_directory_load),
+        directory_cache,
+        directory_watcher,
+        pending_watcher_paths: HashSet::new(),
+        pending_watcher_paths_since: None,
+        recent_changes,
+        show_event_log: false,
+        event_log: EventLog::default(),
+        event_log_filter: EventLogFilter::All,
+        show_message_history: false,
+        message_history_list_state: ListState::default(),
+        path_completion: None,
     };
 
-    let mut app_state_message = AppStateMessage {
-        current_message: String::from("Initial message"),
-        // previous_messages: vec![],
-    };
+    let mut app_state_message = AppStateMessage::default();
 
     app_state
         .list_state_index_of_directory
@@ -118,73 +316,156 @@ fn run(mut terminal: DefaultTerminal) -> Result<()> {
         .file_list_state
         .select(list_state_index_of_initial_directory);
 
-    let (mut notify_watcher, directory_watcher_receiver) =
-        setup_directory_watcher(initial_directory);
+    spawn_terminal_event_reader(app_state.app_event_sender.clone());
 
+    let mut needs_redraw = true;
     loop {
-        let maybe_directory_watcher_receiver_result = directory_watcher_receiver.try_recv();
+        if needs_redraw {
+            if app_state.needs_full_redraw {
+                terminal.clear()?;
+                app_state.needs_full_redraw = false;
+            }
+            terminal
+                .draw(|frame| draw_widgets_to_frame(frame, &mut app_state, &app_state_message))?;
+        }
 
-        match maybe_directory_watcher_receiver_result {
-            Ok(result_event_or_error) => match result_event_or_error {
-                Ok(event) => handle_notify_watcher_event(event, &mut app_state),
+        // block until something happens, or until something time based needs to be redrawn
+        let app_event = match get_time_until_next_redraw(&app_state, &app_state_message) {
+            Some(timeout) => match app_event_receiver.recv_timeout(timeout) {
+                Ok(app_event) => Some(app_event),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break Ok(None),
+            },
+            None => match app_event_receiver.recv() {
+                Ok(app_event) => Some(app_event),
+                Err(_) => break Ok(None),
+            },
+        };
+        // timeouts only happen when something time based needs to be redrawn
+        needs_redraw = app_event.is_none();
 
+        // handle everything that is already queued before drawing again
+        let queued_app_events = app_event.into_iter().chain(app_event_receiver.try_iter());
+        for app_event in queued_app_events {
+            needs_redraw |= does_app_event_need_redraw(&app_event, &app_state);
+            match app_event {
+                AppEvent::Terminal(Event::Key(key)) if key.kind == KeyEventKind::Press => {
+                    let handle_key_event_result = handle_key_event(key, &mut app_state);
+                    if handle_key_event_result == "quit" {
+                        if let Some(cwd_file) = &cli.cwd_file {
+                            fs::write(cwd_file, &app_state.working_directory)?;
+                        }
+                        return Ok(None);
+                    }
+                    if handle_key_event_result == "quit_without_cd" {
+                        return Ok(None);
+                    }
+                    if handle_key_event_result == "choose" {
+                        return Ok(Some(app_state.chosen_paths));
+                    }
+                }
+                // resizes and other terminal events only need a redraw
+                AppEvent::Terminal(_) => {}
+                AppEvent::Watcher(Ok(event)) => handle_notify_watcher_event(event, &mut app_state),
                 // what errors are these?
-                Err(error) => {
-                    error!("error from result_event_or_error: {:?}", error);
+                AppEvent::Watcher(Err(error)) => {
+                    error!("error from directory watcher: {:?}", error);
                 }
-            },
-            Err(error) => {
-                // Ignore Empty message
-                if error.to_string() != "receiving on an empty channel" {
-                    info!("error from try_recv: {:?}", error);
+                AppEvent::DirectoryLoad { load_id, message } => {
+                    handle_directory_load_message(&mut app_state, load_id, message)
                 }
+                AppEvent::ShellCommandFinished {
+                    command_line,
+                    result,
+                } => handle_shell_command_finished(&mut app_state, command_line, result),
+                AppEvent::UiMessage(level, message) => app_state_message.show(level, message),
             }
         }
-        let previous_working_directory = app_state.working_directory.clone();
 
-        terminal.draw(|frame| {
-            draw_widgets_to_frame(frame, &mut app_state, &app_state_message.current_message)
-        })?;
+        needs_redraw |= apply_pending_watcher_changes_if_due(&mut app_state);
+        needs_redraw |= app_state.recent_changes.forget_faded_changes();
+        needs_redraw |= app_state_message.expire_info_message(get_is_in_input_mode(&app_state));
+    }
+}
 
-        let handle_key_event_result = handle_key_event(&mut app_state);
-        if handle_key_event_result == "quit" {
-            break Ok(());
-        }
+/// Key releases don't change anything on screen. Watcher events are only collected, the redraw
+/// happens once they are applied, unless they are shown in the event log.
+fn does_app_event_need_redraw(app_event: &AppEvent, app_state: &AppState) -> bool {
+    match app_event {
+        AppEvent::Terminal(Event::Key(key)) => key.kind == KeyEventKind::Press,
+        AppEvent::Watcher(_) => app_state.show_event_log,
+        _ => true,
+    }
+}
 
-        // UI Message stuff
-        let maybe_result_from_ui_message_receiver = receiver_for_ui_message.try_recv();
-        if let Ok(result) = maybe_result_from_ui_message_receiver {
-            app_state_message.current_message = result;
-        }
+/// Returns how long the main loop may block before something time based, like the loading
+/// indicator, debounced watcher changes, fading highlights or expiring messages, needs to be
+/// handled. None means it can block until the next event.
+fn get_time_until_next_redraw(
+    app_state: &AppState,
+    app_state_message: &AppStateMessage,
+) -> Option<Duration> {
+    let time_until_loading_indicator = app_state.directory_load.as_ref().map(|directory_load| {
+        LOADING_INDICATOR_DELAY
+            .saturating_sub(directory_load.started_at.elapsed())
+            // keep the loaded entry count of the indicator updating
+            .max(Duration::from_millis(100))
+    });
+    let time_until_watcher_changes = get_time_until_pending_watcher_changes_are_due(app_state);
+    let time_until_highlight_fades = app_state.recent_changes.get_time_until_next_stage();
+    let time_until_message_expires =
+        app_state_message.get_time_until_info_message_expires(get_is_in_input_mode(app_state));
 
-        // Directory watcher stuff
-        // if our working directory changed, we need to stop previous directory watcher and start new
-        // one.
-        if previous_working_directory != app_state.working_directory {
-            info!("Our working directory changed! Stopping previous notify_watcher and starting a new one");
-            let unwatch_result = notify_watcher.unwatch(Path::new(&previous_working_directory));
-            match unwatch_result {
-                Ok(_) => {
-                    let new_watch_result = notify_watcher.watch(
-                        Path::new(&app_state.working_directory),
-                        notify::RecursiveMode::NonRecursive,
-                    );
-                    match new_watch_result {
-                        Ok(_) => {
-                            info!(
-                                "Successfully watching new working directory: {:?}",
-                                app_state.working_directory
-                            )
-                        }
-                        Err(error) => {
-                            error!("Failed to watch new working directory: {:?}", error);
-                        }
-                    }
-                }
-                Err(error) => {
-                    error!("Failed to unwatch directory: {:?}", error);
-                }
-            }
-        }
+    [
+        time_until_loading_indicator,
+        time_until_watcher_changes,
+        time_until_highlight_fades,
+        time_until_message_expires,
+    ]
+    .into_iter()
+    .flatten()
+    .min()
+}
+
+/// Returns the absolute directory to start in and, if the user passed a file, the absolute path
+/// of that file
+fn get_initial_directory_and_file(cli: &Cli) -> Result<(String, Option<String>)> {
+    let Some(path) = &cli.path else {
+        return Ok((get_default_start_directory(), None));
+    };
+
+    let canonical_path =
+        fs::canonicalize(path).wrap_err_with(|| format!("Failed to open start path {}", path))?;
+    if canonical_path.is_dir() {
+        return Ok((canonical_path.to_string_lossy().to_string(), None));
+    }
+
+    let parent_directory = canonical_path
+        .parent()
+        .map(|parent| parent.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("/"));
+    Ok((
+        parent_directory,
+        Some(canonical_path.to_string_lossy().to_string()),
+    ))
+}
+
+/// Returns the content of the --select-from file, or of stdin for "-". Stdin is never read
+/// implicitly, it might be a pipe that is never closed, e.g. when started from an editor.
+fn read_path_list_for_selection(cli: &Cli) -> Result<Option<String>> {
+    let read_stdin = || -> Result<Option<String>> {
+        let mut path_list = String::new();
+        stdin()
+            .read_to_string(&mut path_list)
+            .wrap_err("Failed to read paths from stdin")?;
+        Ok(Some(path_list))
+    };
+
+    match cli.select_from.as_deref() {
+        Some("-") => read_stdin(),
+        Some(select_from) => fs::read_to_string(select_from)
+            .map(Some)
+            .wrap_err_with(|| format!("Failed to read paths from {}", select_from)),
+        None => Ok(None),
     }
 }
@@ -0,0 +1,97 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local};
+
+/// Info messages disappear after this long, unless they are the prompt of an input
+pub const INFO_MESSAGE_DURATION: Duration = Duration::from_secs(5);
+
+/// How many messages are kept in the history, older ones are dropped
+const MESSAGE_HISTORY_CAPACITY: usize = 500;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MessageLevel {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl MessageLevel {
+    pub fn get_display_name(self) -> &'static str {
+        match self {
+            MessageLevel::Info => "info",
+            MessageLevel::Success => "success",
+            MessageLevel::Warning => "warning",
+            MessageLevel::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Message {
+    pub level: MessageLevel,
+    pub text: String,
+    pub received_at: Instant,
+    pub time: DateTime<Local>,
+}
+
+/// The message shown in the message bar and the most recent messages of the session
+#[derive(Default)]
+pub struct AppStateMessage {
+    pub previous_messages: VecDeque<Message>,
+    pub current_message: Option<Message>,
+}
+
+impl AppStateMessage {
+    /// Shows the message in the message bar and adds it to the history. An empty message only
+    /// clears the message bar.
+    pub fn show(&mut self, level: MessageLevel, text: String) {
+        if text.is_empty() {
+            self.current_message = None;
+            return;
+        }
+        let message = Message {
+            level,
+            text,
+            received_at: Instant::now(),
+            time: Local::now(),
+        };
+        if self.previous_messages.len() == MESSAGE_HISTORY_CAPACITY {
+            self.previous_messages.pop_front();
+        }
+        self.previous_messages.push_back(message.clone());
+        self.current_message = Some(message);
+    }
+
+    pub fn get_current_text(&self) -> &str {
+        self.current_message
+            .as_ref()
+            .map(|message| message.text.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Clears an expired info message. While an input is active the message is its prompt, so
+    /// it is kept. Returns true if the message was cleared.
+    pub fn expire_info_message(&mut self, is_in_input_mode: bool) -> bool {
+        if self.get_time_until_info_message_expires(is_in_input_mode) != Some(Duration::ZERO) {
+            return false;
+        }
+        self.current_message = None;
+        true
+    }
+
+    /// Returns how long until the current info message expires, None if there is none or it is
+    /// the prompt of the active input
+    pub fn get_time_until_info_message_expires(&self, is_in_input_mode: bool) -> Option<Duration> {
+        if is_in_input_mode {
+            return None;
+        }
+        self.current_message
+            .as_ref()
+            .filter(|message| message.level == MessageLevel::Info)
+            .map(|message| INFO_MESSAGE_DURATION.saturating_sub(message.received_at.elapsed()))
+    }
+}
@@ -0,0 +1,57 @@
+use std::{fs, path::Path};
+
+/// File systems whose changes can happen on another machine or behind a FUSE daemon, so native
+/// watchers like inotify don't see them
+const REMOTE_FILE_SYSTEM_TYPES: [&str; 9] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "9p",
+    "ceph",
+    "glusterfs",
+    "afs",
+];
+
+/// Returns true if the given directory is on a network or FUSE file system according to
+/// `/proc/mounts`. Always false where `/proc/mounts` doesn't exist.
+pub fn is_on_remote_or_fuse_file_system(directory: &str) -> bool {
+    let Ok(mounts) = fs::read_to_string("/proc/mounts") else {
+        return false;
+    };
+    let directory = fs::canonicalize(directory).unwrap_or_else(|_| directory.into());
+
+    // the innermost mount point containing the directory is the one it is on
+    let file_system_type = mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = unescape_mount_field(fields.nth(1)?);
+            let file_system_type = fields.next()?;
+            Some((mount_point, file_system_type))
+        })
+        .filter(|(mount_point, _)| directory.starts_with(Path::new(mount_point)))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, file_system_type)| file_system_type);
+
+    match file_system_type {
+        // fuseblk is used for local block devices (e.g. ntfs-3g), which are only changed by us
+        Some("fuseblk") => false,
+        Some(file_system_type) => {
+            file_system_type == "fuse"
+                || file_system_type.starts_with("fuse.")
+                || REMOTE_FILE_SYSTEM_TYPES.contains(&file_system_type)
+        }
+        None => false,
+    }
+}
+
+/// Spaces, tabs, newlines and backslashes are octal escaped in `/proc/mounts`
+fn unescape_mount_field(field: &str) -> String {
+    field
+        .replace("\\040", " ")
+        .replace("\\011", "\t")
+        .replace("\\012", "\n")
+        .replace("\\134", "\\")
+}
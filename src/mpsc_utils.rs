@@ -2,8 +2,30 @@ use std::sync::mpsc::Sender;
 
 use log::warn;
 
-pub fn send_message_or_panic(sender: &mut Sender<String>, message: String) {
-    let result = sender.send(message);
+use crate::{app_event::AppEvent, message::MessageLevel};
+
+pub fn send_message_or_panic(sender: &mut Sender<AppEvent>, message: String) {
+    send_message_with_level_or_panic(sender, MessageLevel::Info, message);
+}
+
+pub fn send_success_message_or_panic(sender: &mut Sender<AppEvent>, message: String) {
+    send_message_with_level_or_panic(sender, MessageLevel::Success, message);
+}
+
+pub fn send_warning_message_or_panic(sender: &mut Sender<AppEvent>, message: String) {
+    send_message_with_level_or_panic(sender, MessageLevel::Warning, message);
+}
+
+pub fn send_error_message_or_panic(sender: &mut Sender<AppEvent>, message: String) {
+    send_message_with_level_or_panic(sender, MessageLevel::Error, message);
+}
+
+pub fn send_message_with_level_or_panic(
+    sender: &mut Sender<AppEvent>,
+    level: MessageLevel,
+    message: String,
+) {
+    let result = sender.send(AppEvent::UiMessage(level, message));
     match result {
         Ok(_) => {}
         Err(error) => {
@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use glob::Pattern;
+
+use crate::{
+    cmd::{
+        create_shell_command, detect_mime_type, get_open_command_for_system_arch,
+        run_command_in_foreground, shell_quote, spawn_detached,
+    },
+    config::{Config, OpenerRule},
+};
+
+/// Returns every opener rule from the config matching the given file, in config order, followed
+/// by the system default opener.
+pub fn get_matching_openers(config: &Config, file_path: &str) -> Vec<OpenerRule> {
+    let needs_mime_type = config.openers.iter().any(|opener| opener.mime.is_some());
+    // detecting the MIME type spawns a process, so only do it if any rule needs it
+    let mime_type = if needs_mime_type {
+        detect_mime_type(file_path)
+    } else {
+        None
+    };
+
+    config
+        .openers
+        .iter()
+        .filter(|opener| does_opener_match(opener, file_path, mime_type.as_deref()))
+        .cloned()
+        .chain(std::iter::once(get_system_default_opener()))
+        .collect()
+}
+
+fn does_opener_match(opener: &OpenerRule, file_path: &str, mime_type: Option<&str>) -> bool {
+    let path = Path::new(file_path);
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let glob_matches = match &opener.glob {
+        None => true,
+        Some(glob) => {
+            let matched_against = if glob.contains('/') {
+                file_path
+            } else {
+                &file_name
+            };
+            Pattern::new(glob).is_ok_and(|pattern| pattern.matches(matched_against))
+        }
+    };
+
+    let extension_matches = opener.extensions.is_empty() || {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        extension.is_some_and(|extension| {
+            opener.extensions.iter().any(|opener_extension| {
+                opener_extension.trim_start_matches('.').to_lowercase() == extension
+            })
+        })
+    };
+
+    let mime_matches = match &opener.mime {
+        None => true,
+        Some(mime_pattern) => mime_type.is_some_and(|mime_type| {
+            Pattern::new(mime_pattern).is_ok_and(|pattern| pattern.matches(mime_type))
+        }),
+    };
+
+    glob_matches && extension_matches && mime_matches
+}
+
+fn get_system_default_opener() -> OpenerRule {
+    // `start` treats the first quoted argument as the window title
+    let command = if cfg!(windows) {
+        format!("{} \"\" %f", get_open_command_for_system_arch())
+    } else {
+        format!("{} %f", get_open_command_for_system_arch())
+    };
+    OpenerRule {
+        name: Some(String::from("System default")),
+        glob: None,
+        extensions: vec![],
+        mime: None,
+        command,
+        foreground: false,
+    }
+}
+
+/// Runs the given opener for the given file. Foreground openers take over the terminal until
+/// they exit, so the caller needs to redraw afterwards.
+pub fn run_opener(opener: &OpenerRule, file_path: &str) -> Result<(), String> {
+    let quoted_file_path = shell_quote(file_path);
+    let command_line = if opener.command.contains("%f") {
+        opener.command.replace("%f", &quoted_file_path)
+    } else {
+        format!("{} {}", opener.command, quoted_file_path)
+    };
+    let mut command = create_shell_command(&command_line);
+
+    if opener.foreground {
+        match run_command_in_foreground(&mut command) {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(format!(
+                "{} exited with {}",
+                opener.get_display_name(),
+                status
+            )),
+            Err(error) => Err(format!(
+                "Failed to run {}: {}",
+                opener.get_display_name(),
+                error
+            )),
+        }
+    } else {
+        spawn_detached(&mut command)
+            .map_err(|error| format!("Failed to run {}: {}", opener.get_display_name(), error))
+    }
+}
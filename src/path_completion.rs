@@ -0,0 +1,156 @@
+use std::fs;
+
+use ratatui::widgets::ListState;
+
+use crate::{
+    brace_expansion::{escape_name, split_off_last_word},
+    env::get_home_dir,
+    input_action::InputAction,
+    AppState,
+};
+
+/// Candidates of an ambiguous Tab completion. Pressing Tab again cycles through them.
+pub struct PathCompletion {
+    /// The completed paths, as typed before the cursor
+    pub candidates: Vec<String>,
+    pub list_state: ListState,
+    /// The input before the completed word, e.g. the other names of the create prompt
+    input_before_word: String,
+    /// Whether the prompt takes several names, which are escaped then
+    is_word_list: bool,
+}
+
+impl PathCompletion {
+    /// Returns the replacement for the input before the cursor
+    fn get_input(&self, candidate: &str) -> String {
+        get_input_for_candidate(&self.input_before_word, self.is_word_list, candidate)
+    }
+}
+
+/// Resolves a path typed into an input prompt: `~` is expanded, relative paths are relative to
+/// the working directory
+pub fn resolve_input_path(working_directory: &str, input: &str) -> String {
+    if input == "~" || input.starts_with("~/") {
+        if let Ok(home_dir) = get_home_dir() {
+            return home_dir + &input[1..];
+        }
+    }
+    if input.starts_with('/') {
+        input.to_string()
+    } else {
+        format!("{}/{}", working_directory, input)
+    }
+}
+
+/// Completes the path before the cursor of the input prompt. In the create prompt, which takes
+/// several names, only the last name is completed and the inserted name is escaped. Unambiguous
+/// completions and the common prefix of ambiguous ones are inserted directly, pressing Tab again
+/// cycles through the candidates.
+pub fn complete_path(app_state: &mut AppState, backwards: bool) {
+    if let Some(path_completion) = &mut app_state.path_completion {
+        let candidate_count = path_completion.candidates.len();
+        let index = match path_completion.list_state.selected() {
+            None if backwards => candidate_count - 1,
+            None => 0,
+            Some(index) if backwards => (index + candidate_count - 1) % candidate_count,
+            Some(index) => (index + 1) % candidate_count,
+        };
+        path_completion.list_state.select(Some(index));
+        let input = path_completion.get_input(&path_completion.candidates[index]);
+        app_state.user_input.replace_before_cursor(input);
+        return;
+    }
+
+    let text_before_cursor = app_state.user_input.get_text_before_cursor();
+    let is_word_list = app_state.input_action == InputAction::CreateFile;
+    let (input_before_word, word) = if is_word_list {
+        let (word_start, word) = split_off_last_word(text_before_cursor);
+        (text_before_cursor[..word_start].to_string(), word)
+    } else {
+        (String::new(), text_before_cursor.to_string())
+    };
+
+    let candidates = get_path_candidates(&app_state.working_directory, &word);
+    match candidates.as_slice() {
+        [] => {}
+        [candidate] => {
+            let input = get_input_for_candidate(&input_before_word, is_word_list, candidate);
+            app_state.user_input.replace_before_cursor(input);
+        }
+        _ => {
+            let common_prefix = get_common_prefix(&candidates);
+            if common_prefix.len() > word.len() {
+                let input =
+                    get_input_for_candidate(&input_before_word, is_word_list, &common_prefix);
+                app_state.user_input.replace_before_cursor(input);
+            }
+            app_state.path_completion = Some(PathCompletion {
+                candidates,
+                list_state: ListState::default(),
+                input_before_word,
+                is_word_list,
+            });
+        }
+    }
+}
+
+fn get_input_for_candidate(input_before_word: &str, is_word_list: bool, candidate: &str) -> String {
+    if is_word_list {
+        format!("{}{}", input_before_word, escape_name(candidate))
+    } else {
+        format!("{}{}", input_before_word, candidate)
+    }
+}
+
+/// Returns the typed directory part joined with every entry of that directory starting with the
+/// typed name part. Directories get a trailing slash, hidden entries are only returned if the
+/// name part starts with a dot.
+fn get_path_candidates(working_directory: &str, input: &str) -> Vec<String> {
+    let (directory_part, name_part) = match input.rfind('/') {
+        Some(index) => input.split_at(index + 1),
+        None => ("", input),
+    };
+    let directory = if directory_part.is_empty() {
+        working_directory.to_string()
+    } else {
+        resolve_input_path(working_directory, directory_part)
+    };
+
+    let Ok(read_dir) = fs::read_dir(&directory) else {
+        return vec![];
+    };
+    let mut candidates: Vec<String> = read_dir
+        .filter_map(|dir_entry| dir_entry.ok())
+        .filter_map(|dir_entry| {
+            let name = dir_entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(name_part)
+                || (name.starts_with('.') && !name_part.starts_with('.'))
+            {
+                return None;
+            }
+            let is_dir = dir_entry.path().is_dir();
+            let suffix = if is_dir { "/" } else { "" };
+            Some(format!("{}{}{}", directory_part, name, suffix))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+fn get_common_prefix(candidates: &[String]) -> String {
+    let Some((first, rest)) = candidates.split_first() else {
+        return String::new();
+    };
+    let mut common_prefix_len = first.len();
+    for candidate in rest {
+        common_prefix_len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, first_char), char)| first_char == char)
+            .last()
+            .map(|((index, first_char), _)| index + first_char.len_utf8())
+            .unwrap_or(0)
+            .min(common_prefix_len);
+    }
+    first[..common_prefix_len].to_string()
+}
@@ -0,0 +1,68 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// How many steps the highlight of a changed file fades through
+pub const HIGHLIGHT_STAGES: u32 = 3;
+
+/// Remembers when files were created or modified, so the 'Files' window can highlight them until
+/// the highlight has faded
+pub struct RecentChanges {
+    changed_at: HashMap<String, Instant>,
+    highlight_duration: Duration,
+}
+
+impl RecentChanges {
+    /// A highlight duration of zero disables highlighting
+    pub fn new(highlight_duration: Duration) -> Self {
+        RecentChanges {
+            changed_at: HashMap::new(),
+            highlight_duration,
+        }
+    }
+
+    pub fn record(&mut self, path: String) {
+        if self.highlight_duration.is_zero() {
+            return;
+        }
+        self.changed_at.insert(path, Instant::now());
+    }
+
+    /// Returns how far the highlight of the given file has faded, 0 being the strongest
+    /// highlight. None if it isn't highlighted (anymore).
+    pub fn get_highlight_stage(&self, path: &str) -> Option<u32> {
+        let elapsed = self.changed_at.get(path)?.elapsed();
+        let stage = elapsed.as_millis() * HIGHLIGHT_STAGES as u128
+            / self.highlight_duration.as_millis().max(1);
+        if stage < HIGHLIGHT_STAGES as u128 {
+            Some(stage as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Forgets the changes whose highlight has faded. Returns true if there were any.
+    pub fn forget_faded_changes(&mut self) -> bool {
+        let previous_len = self.changed_at.len();
+        let highlight_duration = self.highlight_duration;
+        self.changed_at
+            .retain(|_, changed_at| changed_at.elapsed() < highlight_duration);
+        self.changed_at.len() != previous_len
+    }
+
+    /// Returns how long until the next highlight fades to its next stage, None if nothing is
+    /// highlighted
+    pub fn get_time_until_next_stage(&self) -> Option<Duration> {
+        let stage_duration = self.highlight_duration / HIGHLIGHT_STAGES;
+        self.changed_at
+            .values()
+            .map(|changed_at| {
+                let elapsed = changed_at.elapsed();
+                let elapsed_stages = elapsed.as_millis() / stage_duration.as_millis().max(1);
+                let next_stage_at = stage_duration * (elapsed_stages as u32 + 1);
+                next_stage_at.saturating_sub(elapsed)
+            })
+            .min()
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    fs::{self, create_dir_all},
+    io::{ErrorKind, Write},
+    path::Path,
+};
+
+use chrono::Local;
+
+use crate::env::get_user_name;
+
+#[derive(Clone)]
+pub struct Template {
+    pub name: String,
+    pub full_path: String,
+}
+
+/// Returns the directory the templates are read from, `templates` next to the config file in use
+pub fn get_templates_dir(config_path: &str) -> String {
+    Path::new(config_path)
+        .with_file_name("templates")
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Returns the files of the templates directory sorted by name. A missing directory has no
+/// templates.
+pub fn get_templates(templates_dir: &str) -> Result<Vec<Template>, String> {
+    let read_dir = match fs::read_dir(templates_dir) {
+        Ok(read_dir) => read_dir,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(error) => return Err(format!("Failed to read {}: {}", templates_dir, error)),
+    };
+
+    let mut templates: Vec<Template> = read_dir
+        .filter_map(|dir_entry| dir_entry.ok())
+        .filter(|dir_entry| dir_entry.path().is_file())
+        .map(|dir_entry| Template {
+            name: dir_entry.file_name().to_string_lossy().to_string(),
+            full_path: dir_entry.path().to_string_lossy().to_string(),
+        })
+        .collect();
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+/// Creates a new file with the content of the template and every missing parent directory.
+/// Variables in text templates are expanded, see `expand_template_variables`. Existing files are
+/// never overwritten.
+pub fn create_file_from_template(template: &Template, full_path: &str) -> Result<(), String> {
+    let content = fs::read(&template.full_path)
+        .map_err(|error| format!("Failed to read template {}: {}", template.name, error))?;
+    // binary templates are copied as they are
+    let content = match String::from_utf8(content) {
+        Ok(text) => expand_template_variables(&text, full_path).into_bytes(),
+        Err(error) => error.into_bytes(),
+    };
+
+    if let Some(parent) = Path::new(full_path).parent() {
+        create_dir_all(parent).map_err(|error| error.to_string())?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(full_path)
+        .map_err(|error| match error.kind() {
+            ErrorKind::AlreadyExists => format!("{} already exists", full_path),
+            _ => error.to_string(),
+        })?;
+    file.write_all(&content).map_err(|error| error.to_string())
+}
+
+/// Replaces `{{date}}` (YYYY-MM-DD), `{{year}}`, `{{file_name}}`, `{{file_stem}}` (the file name
+/// without its extension) and `{{user}}` with the values for the new file. Unknown variables are
+/// kept as they are.
+fn expand_template_variables(text: &str, full_path: &str) -> String {
+    let path = Path::new(full_path);
+    let file_name = path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file_stem = path
+        .file_stem()
+        .map(|file_stem| file_stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let now = Local::now();
+
+    let variables = [
+        ("date", now.format("%Y-%m-%d").to_string()),
+        ("year", now.format("%Y").to_string()),
+        ("file_name", file_name),
+        ("file_stem", file_stem),
+        ("user", get_user_name().unwrap_or_default()),
+    ];
+
+    // a single pass, so values containing `{{...}}` (e.g. a file name) are never expanded again
+    let mut expanded_text = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        expanded_text.push_str(&rest[..start]);
+        let token = &rest[start..];
+        let value = token[2..].split_once("}}").and_then(|(name, _)| {
+            variables
+                .iter()
+                .find(|(variable_name, _)| *variable_name == name)
+                .map(|(_, value)| (name, value))
+        });
+        match value {
+            Some((name, value)) => {
+                expanded_text.push_str(value);
+                rest = &token[name.len() + 4..];
+            }
+            // e.g. `{{{date}}}` still contains a variable after the first brace
+            None => {
+                expanded_text.push('{');
+                rest = &token[1..];
+            }
+        }
+    }
+    expanded_text.push_str(rest);
+    expanded_text
+}
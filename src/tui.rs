@@ -0,0 +1,49 @@
+use std::{
+    io::{stderr, stdout, Error, IsTerminal, Write},
+    panic,
+};
+
+use crossterm::{
+    cursor::Show,
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use ratatui::{backend::CrosstermBackend, Terminal};
+
+pub type Tui = Terminal<CrosstermBackend<Box<dyn Write + Send>>>;
+
+/// The TUI is drawn to stdout, unless stdout is redirected (e.g. when the chosen paths are
+/// written to stdout in chooser mode). Then stderr is used instead.
+fn get_tui_writer() -> Box<dyn Write + Send> {
+    if stdout().is_terminal() {
+        Box::new(stdout())
+    } else {
+        Box::new(stderr())
+    }
+}
+
+/// Enters raw mode and the alternate screen and installs a panic hook that restores the terminal
+/// before printing the panic
+pub fn init_tui() -> Result<Tui, Error> {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |panic_info| {
+        restore_tui();
+        previous_hook(panic_info);
+    }));
+
+    resume_tui()?;
+    Terminal::new(CrosstermBackend::new(get_tui_writer()))
+}
+
+/// Leaves raw mode and the alternate screen. Errors are ignored, as this is also used while
+/// panicking and there is nothing sensible left to do.
+pub fn restore_tui() {
+    let _ = disable_raw_mode();
+    let _ = execute!(get_tui_writer(), LeaveAlternateScreen, Show);
+}
+
+/// Enters raw mode and the alternate screen again after [`restore_tui`]
+pub fn resume_tui() -> Result<(), Error> {
+    enable_raw_mode()?;
+    execute!(get_tui_writer(), EnterAlternateScreen)
+}
@@ -1,11 +1,16 @@
+use std::{fs, path::Path};
+
+use log::warn;
+
 use crate::{
+    directory_loader::{start_directory_load, DirectoryLoadMessage},
+    env::get_default_start_directory,
     file::{
-        delete_file, get_files_for_dir, get_parent_dir, is_path_directory,
-        sort_file_paths_dirs_first_then_files,
+        delete_file, get_parent_dir, is_path_directory, sort_file_paths_dirs_first_then_files, File,
     },
     input_action::InputAction,
-    mpsc_utils::send_message_or_panic,
-    widget::{get_selected_item_from_list_state, reset_current_message_and_input, Window},
+    mpsc_utils::{send_error_message_or_panic, send_warning_message_or_panic},
+    widget::{reset_current_message_and_input, reset_input, Window},
     AppState,
 };
 
@@ -17,34 +22,23 @@ pub fn enter_directory(app_state: &mut AppState) {
     match selected_file_index {
         None => {}
         Some(index) => {
+            // the directory might still be loading
+            let Some(selected_file) = app_state.files.get(index) else {
+                return;
+            };
+            let selected_file_full_path = selected_file.full_path.clone();
             app_state
                 .list_state_index_of_directory
                 .insert(app_state.working_directory.clone(), index);
-            let selected_file = app_state
-                .files
-                .get(index)
-                .expect("the selected file should exist");
-            let selected_file_full_path = &selected_file.full_path;
 
-            let index_of_dir_being_entered = app_state
-                .list_state_index_of_directory
-                .get(selected_file_full_path)
-                .or(Some(&0));
-
-            if is_path_directory(&selected_file.full_path) {
-                let maybe_files =
-                    get_files_for_dir(&selected_file.full_path, app_state.show_hidden_files);
-                match maybe_files {
-                    Ok(files) => {
-                        app_state
-                            .file_list_state
-                            .select(index_of_dir_being_entered.copied());
-                        app_state.working_directory = selected_file.full_path.to_string();
-                        app_state.files = files;
-                    }
+            if is_path_directory(&selected_file_full_path) {
+                // opening the directory is cheap and catches permission errors before we leave
+                // the current directory, the actual listing happens in the background
+                match fs::read_dir(&selected_file_full_path) {
+                    Ok(_) => change_working_directory(app_state, selected_file_full_path),
                     Err(error) => {
-                        send_message_or_panic(
-                            &mut app_state.sender_for_ui_message,
+                        send_error_message_or_panic(
+                            &mut app_state.app_event_sender,
                             format!("Failed to enter directory: {:?}", error),
                         );
                     }
@@ -55,35 +49,135 @@ pub fn enter_directory(app_state: &mut AppState) {
 }
 
 pub fn navigate_to_parent_directory(app_state: &mut AppState) {
-    app_state.working_directory = get_parent_dir(&app_state.working_directory);
-    refresh_files_for_working_directory(app_state);
+    let parent_directory = get_parent_dir(&app_state.working_directory);
+    change_working_directory(app_state, parent_directory);
+}
 
+/// Makes the given directory the working directory. Its listing is taken from the directory
+/// cache if possible, otherwise it is listed in the background. The remembered index of the
+/// directory is selected, while the listing streams in the cursor stays on the file under it.
+pub fn change_working_directory(app_state: &mut AppState, directory: String) {
     let index = app_state
         .list_state_index_of_directory
-        .get(&app_state.working_directory)
-        .or(Some(&0));
+        .get(&directory)
+        .copied()
+        .unwrap_or(0);
+
+    // only complete listings of directories that still exist are cached, they are kept up to date
+    // by watching them
+    let previous_directory = std::mem::replace(&mut app_state.working_directory, directory.clone());
+    if app_state.directory_load.is_none() && Path::new(&previous_directory).is_dir() {
+        let evicted_directories = app_state.directory_cache.insert(
+            previous_directory,
+            std::mem::take(&mut app_state.files),
+            app_state.show_hidden_files,
+            app_state.sort_mode,
+        );
+        for evicted_directory in evicted_directories {
+            app_state.directory_watcher.unwatch(&evicted_directory);
+        }
+    } else {
+        app_state.directory_watcher.unwatch(&previous_directory);
+    }
+    app_state.directory_watcher.watch(&directory);
+
+    let cached_files = app_state.directory_cache.take(
+        &directory,
+        app_state.show_hidden_files,
+        app_state.sort_mode,
+    );
+    match cached_files {
+        Some(files) => {
+            app_state.files = files;
+            app_state
+                .file_list_state
+                .select(Some(index.min(app_state.files.len().saturating_sub(1))));
+            // replacing a previous load cancels it
+            app_state.directory_load = None;
+        }
+        None => {
+            let mut directory_load = start_directory_load(
+                directory,
+                app_state.show_hidden_files,
+                true,
+                app_state.app_event_sender.clone(),
+            );
+            directory_load.index_to_select = Some(index);
 
-    app_state.file_list_state.select(index.copied());
+            app_state.files = vec![];
+            app_state.file_list_state.select(Some(index));
+            app_state.directory_load = Some(directory_load);
+        }
+    }
+}
+
+/// Moves to the nearest ancestor of the working directory that still exists, if the working
+/// directory was removed or renamed by another process. Returns true if it had to move.
+pub fn recover_from_missing_working_directory(app_state: &mut AppState) -> bool {
+    let missing_directory = app_state.working_directory.clone();
+    if Path::new(&missing_directory).is_dir() {
+        return false;
+    }
+
+    let surviving_ancestor = Path::new(&missing_directory)
+        .ancestors()
+        .skip(1)
+        .find(|ancestor| ancestor.is_dir())
+        .map(|ancestor| ancestor.to_string_lossy().to_string())
+        .unwrap_or_else(get_default_start_directory);
+    warn!(
+        "Working directory {:?} disappeared, moving to {:?}",
+        missing_directory, surviving_ancestor
+    );
+
+    // a listing of the missing directory can't succeed anymore
+    app_state.directory_load = None;
+    app_state.directory_watcher.unwatch(&missing_directory);
+    app_state.directory_cache.invalidate(&missing_directory);
+    change_working_directory(app_state, surviving_ancestor.clone());
+
+    send_warning_message_or_panic(
+        &mut app_state.app_event_sender,
+        format!(
+            "{} no longer exists, moved to {}",
+            missing_directory, surviving_ancestor
+        ),
+    );
+    true
 }
 
 pub fn get_is_in_input_mode(app_state: &AppState) -> bool {
     app_state.input_action != InputAction::None
 }
 
-pub fn delete_currently_selected_file(app_state: &mut AppState) {
-    let file = get_selected_item_from_list_state(&app_state.file_list_state, &app_state.files);
+/// Takes the file the running delete or rename prompt was opened for. Returns None and warns if
+/// it no longer exists, e.g. because another process removed or renamed it meanwhile.
+pub fn take_prompted_file(app_state: &mut AppState) -> Option<File> {
+    let file = app_state.prompted_file.take()?;
+    if Path::new(&file.full_path).symlink_metadata().is_err() {
+        send_warning_message_or_panic(
+            &mut app_state.app_event_sender,
+            format!("{} no longer exists, nothing was changed", file.full_path),
+        );
+        reset_input(app_state);
+        return None;
+    }
+    Some(file)
+}
 
-    let delete_result = delete_file(file);
+pub fn delete_prompted_file(app_state: &mut AppState) {
+    let Some(file) = take_prompted_file(app_state) else {
+        return;
+    };
+
+    let delete_result = delete_file(&file);
     match delete_result {
         Ok(_) => {
-            let new_files =
-                get_files_for_dir(&app_state.working_directory, app_state.show_hidden_files)
-                    .expect("can get files in same directory when deleting a file");
-            app_state.files = new_files;
+            refresh_files_for_working_directory(app_state);
         }
         Err(err) => {
-            send_message_or_panic(
-                &mut app_state.sender_for_ui_message,
+            send_error_message_or_panic(
+                &mut app_state.app_event_sender,
                 format!(
                     "Failed to delete file {:?}\nError: {:?}",
                     file.full_path, err
@@ -119,8 +213,8 @@ pub fn delete_selected_files(app_state: &mut AppState) {
     }
     if failed_count != 0 {
         let thing = if failed_count == 1 { "file" } else { "files" };
-        send_message_or_panic(
-            &mut app_state.sender_for_ui_message,
+        send_error_message_or_panic(
+            &mut app_state.app_event_sender,
             format!("Failed to delete {} {}.", failed_count, thing),
         );
     } else {
@@ -130,13 +224,156 @@ pub fn delete_selected_files(app_state: &mut AppState) {
     refresh_files_for_working_directory(app_state);
 }
 
-/// Only use this function if you are sure the new working directory can be read by the current
-/// user, otherwise this function may panic
+/// Lists the working directory again in the background. The current file list stays until the
+/// new listing is finished, unless the working directory is still being entered. The cursor stays
+/// on the selected file.
 pub fn refresh_files_for_working_directory(app_state: &mut AppState) {
-    let files = get_files_for_dir(&app_state.working_directory, app_state.show_hidden_files)
-        .expect("can refresh files in new working directory");
-    let sorted_files = sort_file_paths_dirs_first_then_files(&files);
-    app_state.files = sorted_files;
+    start_refresh_of_working_directory(app_state, None);
+}
+
+/// Like [`refresh_files_for_working_directory`], but moves the cursor to the given path once the
+/// listing is finished, e.g. to a just created file
+pub fn refresh_files_and_select_path(app_state: &mut AppState, path_to_select: String) {
+    start_refresh_of_working_directory(app_state, Some(path_to_select));
+}
+
+fn start_refresh_of_working_directory(app_state: &mut AppState, path_to_select: Option<String>) {
+    // renaming an ancestor of the working directory doesn't cause watcher events, it is noticed
+    // here at the latest
+    if recover_from_missing_working_directory(app_state) {
+        return;
+    }
+    let is_entering_directory = app_state
+        .directory_load
+        .as_ref()
+        .is_some_and(|directory_load| directory_load.stream_into_files);
+
+    // a selection the replaced load still wanted to make (e.g. of a just created file) wins over
+    // the cursor. While entering a directory the cursor is only a remembered index.
+    let path_to_select = path_to_select
+        .or_else(|| {
+            app_state
+                .directory_load
+                .as_mut()
+                .and_then(|directory_load| directory_load.path_to_select.take())
+        })
+        .or_else(|| {
+            if is_entering_directory {
+                None
+            } else {
+                get_selected_file_path(app_state)
+            }
+        });
+    if is_entering_directory {
+        app_state.files = vec![];
+    }
+
+    let mut directory_load = start_directory_load(
+        app_state.working_directory.clone(),
+        app_state.show_hidden_files,
+        is_entering_directory,
+        app_state.app_event_sender.clone(),
+    );
+    directory_load.path_to_select = path_to_select;
+    directory_load.index_to_select = app_state.file_list_state.selected();
+    app_state.directory_load = Some(directory_load);
+}
+
+/// Returns the full path of the file under the cursor in the 'Files' window
+pub fn get_selected_file_path(app_state: &AppState) -> Option<String> {
+    let selected_index = app_state.file_list_state.selected()?;
+    app_state
+        .files
+        .get(selected_index)
+        .map(|file| file.full_path.clone())
+}
+
+/// Moves the cursor in the 'Files' window to the given path. Returns false if it isn't listed.
+pub fn select_file_by_path(app_state: &mut AppState, path: &str) -> bool {
+    let index = app_state
+        .files
+        .iter()
+        .position(|file| file.full_path == path);
+    if index.is_some() {
+        app_state.file_list_state.select(index);
+    }
+    index.is_some()
+}
+
+/// Applies a result of a directory listing to the file list. Results of stale listings are
+/// ignored.
+pub fn handle_directory_load_message(
+    app_state: &mut AppState,
+    load_id: u64,
+    message: DirectoryLoadMessage,
+) {
+    let Some(directory_load) = &mut app_state.directory_load else {
+        return;
+    };
+    if directory_load.id != load_id {
+        return;
+    }
+
+    match message {
+        DirectoryLoadMessage::Batch(files) => {
+            directory_load.loaded_count += files.len();
+            if directory_load.stream_into_files {
+                app_state.files.extend(files);
+            } else {
+                directory_load.loaded_files.extend(files);
+            }
+        }
+        DirectoryLoadMessage::Finished => {
+            let Some(mut directory_load) = app_state.directory_load.take() else {
+                return;
+            };
+            // e.g. an ancestor was renamed while listing
+            if recover_from_missing_working_directory(app_state) {
+                return;
+            }
+            // streamed entries are unsorted, the cursor must stay on the file it is on, so keys
+            // pressed while loading (e.g. D or r) act on the file they were pressed on
+            let streamed_selected_path = if directory_load.stream_into_files {
+                get_selected_file_path(app_state)
+            } else {
+                None
+            };
+            let mut files = if directory_load.stream_into_files {
+                std::mem::take(&mut app_state.files)
+            } else {
+                std::mem::take(&mut directory_load.loaded_files)
+            };
+            sort_file_paths_dirs_first_then_files(&mut files, app_state.sort_mode);
+            app_state.files = files;
+
+            let index_of_path_to_select = directory_load
+                .path_to_select
+                .take()
+                .or(streamed_selected_path)
+                .and_then(|path| {
+                    app_state
+                        .files
+                        .iter()
+                        .position(|file| file.full_path == path)
+                });
+            let index = index_of_path_to_select
+                .or(directory_load.index_to_select)
+                .unwrap_or(0)
+                .min(app_state.files.len().saturating_sub(1));
+            app_state.file_list_state.select(Some(index));
+        }
+        DirectoryLoadMessage::Failed(error) => {
+            let directory = directory_load.directory.clone();
+            app_state.directory_load = None;
+            if recover_from_missing_working_directory(app_state) {
+                return;
+            }
+            send_error_message_or_panic(
+                &mut app_state.app_event_sender,
+                format!("Failed to read directory {}: {}", directory, error),
+            );
+        }
+    }
 }
 
 pub fn refresh_list_state_index_of_directory(app_state: &mut AppState, current_window: Window) {
@@ -1,12 +1,28 @@
 use crate::{
-    file::File, input_action::InputAction, keys::KEYS, mpsc_utils::send_message_or_panic, AppState,
+    bulk_rename::get_bulk_rename_preview,
+    file::{format_file_size, File},
+    input_action::InputAction,
+    keys::KEYS,
+    message::{AppStateMessage, Message, MessageLevel},
+    mpsc_utils::send_message_or_panic,
+    path_completion::PathCompletion,
+    recent_changes::RecentChanges,
+    AppState,
 };
 
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
 use ratatui::{
-    layout::{Constraint, Direction, Flex, Layout, Position, Rect},
+    layout::{Constraint, Direction, Flex, Layout, Margin, Position, Rect},
     style::{Color, Modifier, Style, Stylize},
-    text::Line,
-    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    text::{Line, Span, Text},
+    widgets::{
+        Block, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, Table,
+    },
     Frame,
 };
 
@@ -20,31 +36,56 @@ const SELECTED_STYLE: Style = Style::new()
     .add_modifier(Modifier::BOLD)
     .fg(Color::LightGreen);
 
+/// Styles of changed files, from just changed to almost faded
+const CHANGED_FILE_STYLES: [Style; 3] = [
+    Style::new().add_modifier(Modifier::BOLD).fg(Color::Yellow),
+    Style::new().fg(Color::Yellow),
+    Style::new().add_modifier(Modifier::DIM).fg(Color::Yellow),
+];
+
+pub const LOADING_INDICATOR_DELAY: Duration = Duration::from_millis(200);
+
+const SIZE_COLUMN_WIDTH: usize = 10;
+const MODIFIED_COLUMN_WIDTH: usize = 16;
+/// The size and modified columns are hidden if less space than this is left for the file names
+const MIN_NAME_COLUMN_WIDTH: usize = 20;
+
 // Draws all needed widgets to the passed frame
-pub fn draw_widgets_to_frame(frame: &mut Frame, app_state: &mut AppState, current_message: &str) {
+pub fn draw_widgets_to_frame(
+    frame: &mut Frame,
+    app_state: &mut AppState,
+    app_state_message: &AppStateMessage,
+) {
+    let current_message = app_state_message.get_current_text();
     let files_block_border_style = if app_state.current_window == Window::Files {
         Style::new().light_green()
     } else {
         Style::new()
     };
+    // only show the loading indicator for slow listings, so quick ones don't flicker
+    let files_block_title = match &app_state.directory_load {
+        Some(directory_load) if directory_load.started_at.elapsed() >= LOADING_INDICATOR_DELAY => {
+            format!("Files (loading {} entries…)", directory_load.loaded_count)
+        }
+        _ => String::from("Files"),
+    };
     let files_block = Block::new()
-        .title("Files")
+        .title(files_block_title)
         .borders(Borders::all())
         .border_style(files_block_border_style);
 
+    let watch_description = app_state
+        .directory_watcher
+        .get_watch_description(&app_state.working_directory);
     let current_dir_block = Block::new()
         .title("Current directory")
+        .title(Line::from(watch_description).right_aligned())
         .borders(Borders::all())
         .border_style(Style::new().light_green());
 
     let current_directory_paragraph =
         Paragraph::new(app_state.working_directory.clone()).block(current_dir_block);
 
-    let files_list_widget_with_block = List::new(app_state.files.clone())
-        .block(files_block.clone())
-        .highlight_style(SELECTED_STYLE)
-        .highlight_symbol(">");
-
     let root_layout = Layout::vertical([Constraint::Percentage(90), Constraint::Percentage(10)]);
     let [upper_layout, lower_layout] = root_layout.areas(frame.area());
 
@@ -62,22 +103,35 @@ pub fn draw_widgets_to_frame(frame: &mut Frame, app_state: &mut AppState, curren
     let current_message_or_user_input_widget_title = if app_state.input_action == InputAction::None
     {
         "Current message".to_string()
+    } else if let Some(search) = &app_state.input_history.search {
+        format!("(reverse-i-search) '{}'", search.query)
     } else {
         current_message.to_string()
     };
 
-    let text = if app_state.input_action != InputAction::None {
-        app_state.user_input.clone()
+    // long inputs are scrolled horizontally, inside the borders
+    let input_width = lower_layout.width.saturating_sub(2) as usize;
+    let (text, input_cursor_column) = if app_state.input_action != InputAction::None {
+        app_state.user_input.get_visible_text(input_width)
     } else {
-        current_message.to_string()
+        (current_message.to_string(), 0)
     };
 
-    let current_message_or_user_input_widget = Paragraph::new(text).block(
-        Block::new()
-            .borders(Borders::all())
-            .title(current_message_or_user_input_widget_title)
-            .title_bottom(Line::from("Press c to show the cheatsheet").right_aligned()),
-    );
+    // the prompt of an input is shown as the title, the input itself isn't styled
+    let text_style = match &app_state_message.current_message {
+        Some(message) if app_state.input_action == InputAction::None => {
+            get_message_style(message.level)
+        }
+        _ => Style::new(),
+    };
+
+    let current_message_or_user_input_widget = Paragraph::new(Text::styled(text, text_style))
+        .block(
+            Block::new()
+                .borders(Borders::all())
+                .title(current_message_or_user_input_widget_title)
+                .title_bottom(Line::from("Press c to show the cheatsheet").right_aligned()),
+        );
     frame.render_widget(current_message_or_user_input_widget, lower_layout);
 
     let inner_left_layout = Layout::default()
@@ -85,19 +139,37 @@ pub fn draw_widgets_to_frame(frame: &mut Frame, app_state: &mut AppState, curren
         .constraints(vec![Constraint::Min(3), Constraint::Percentage(93)])
         .split(inner_upper_layout[0]);
 
-    frame.render_stateful_widget(
-        &files_list_widget_with_block,
-        inner_left_layout[1],
+    let files_area = if app_state.show_event_log {
+        let [files_area, event_log_area] =
+            Layout::vertical([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(inner_left_layout[1]);
+        draw_event_log(frame, event_log_area, app_state);
+        files_area
+    } else {
+        inner_left_layout[1]
+    };
+
+    // borders, highlight symbol and scrollbar
+    let name_column_width = (files_area.width as usize)
+        .saturating_sub(4 + 2 + SIZE_COLUMN_WIDTH + 2 + MODIFIED_COLUMN_WIDTH);
+    let show_details = name_column_width >= MIN_NAME_COLUMN_WIDTH;
+    let recent_changes = &app_state.recent_changes;
+    render_virtualized_list(
+        frame,
+        files_area,
+        files_block,
+        &app_state.files,
+        |file| {
+            get_file_list_item(
+                file,
+                show_details.then_some(name_column_width),
+                recent_changes,
+            )
+        },
         &mut app_state.file_list_state,
     );
 
     if app_state.show_selected_files_window {
-        let selected_files_list_item: Vec<ListItem> = app_state
-            .selected_files
-            .iter()
-            .map(|selected_file| ListItem::new(selected_file.full_path.clone()))
-            .collect();
-
         let selected_files_block_style = if app_state.current_window == Window::SelectedFiles {
             Style::new().light_green()
         } else {
@@ -108,14 +180,12 @@ pub fn draw_widgets_to_frame(frame: &mut Frame, app_state: &mut AppState, curren
             .borders(Borders::all())
             .border_style(selected_files_block_style);
 
-        let selected_files_list_widget = List::new(selected_files_list_item)
-            .block(selected_files_block)
-            .highlight_style(SELECTED_STYLE)
-            .highlight_symbol(">");
-
-        frame.render_stateful_widget(
-            selected_files_list_widget,
+        render_virtualized_list(
+            frame,
             inner_upper_layout[1],
+            selected_files_block,
+            &app_state.selected_files,
+            |selected_file| ListItem::new(selected_file.full_path.as_str()),
             &mut app_state.selected_files_list_state,
         );
     }
@@ -123,11 +193,15 @@ pub fn draw_widgets_to_frame(frame: &mut Frame, app_state: &mut AppState, curren
     frame.render_widget(&current_directory_paragraph, inner_left_layout[0]);
     if app_state.input_action != InputAction::None {
         frame.set_cursor_position(Position::new(
-            lower_layout.x + app_state.user_input.len() as u16 + 1,
+            lower_layout.x + input_cursor_column as u16 + 1,
             lower_layout.y + 1,
         ))
     }
 
+    if let Some(path_completion) = &mut app_state.path_completion {
+        draw_path_completion_popup(frame, lower_layout, path_completion);
+    }
+
     if app_state.show_cheatsheet {
         let items: Vec<ListItem> = KEYS.iter().map(|key| ListItem::new(*key)).collect();
 
@@ -139,37 +213,401 @@ pub fn draw_widgets_to_frame(frame: &mut Frame, app_state: &mut AppState, curren
         frame.render_widget(Clear, area);
         frame.render_widget(list, area);
     }
+
+    if app_state.show_open_with_popup {
+        let items: Vec<ListItem> = app_state
+            .open_with_openers
+            .iter()
+            .map(|opener| {
+                let mode = if opener.foreground {
+                    "foreground"
+                } else {
+                    "detached"
+                };
+                ListItem::new(format!("{} ({})", opener.get_display_name(), mode))
+            })
+            .collect();
+
+        let block = Block::bordered()
+            .title("Open with...")
+            .title_bottom(Line::from("Enter to open, Esc to close").right_aligned());
+        let area = popup_area(frame.area(), 50, 40);
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(SELECTED_STYLE)
+            .highlight_symbol(">");
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut app_state.open_with_list_state);
+    }
+
+    if app_state.show_template_popup {
+        let items: Vec<ListItem> = app_state
+            .templates
+            .iter()
+            .map(|template| ListItem::new(template.name.as_str()))
+            .collect();
+
+        let block = Block::bordered()
+            .title("New from template")
+            .title_bottom(Line::from("Enter to choose, Esc to close").right_aligned());
+        let area = popup_area(frame.area(), 50, 40);
+
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(SELECTED_STYLE)
+            .highlight_symbol(">");
+        frame.render_widget(Clear, area);
+        frame.render_stateful_widget(list, area, &mut app_state.template_list_state);
+    }
+
+    if app_state.show_command_output_popup {
+        let block = Block::bordered()
+            .title(app_state.command_output_title.clone())
+            .title_bottom(Line::from("j/k to scroll, Esc to close").right_aligned());
+        let area = popup_area(frame.area(), 80, 70);
+
+        let paragraph = Paragraph::new(app_state.command_output.clone())
+            .block(block)
+            .scroll((app_state.command_output_scroll, 0));
+        frame.render_widget(Clear, area);
+        frame.render_widget(paragraph, area);
+    }
+
+    if app_state.show_message_history {
+        let block = Block::bordered()
+            .title("Message history")
+            .title_bottom(Line::from("j/k to scroll, Esc to close").left_aligned());
+        let area = popup_area(frame.area(), 80, 70);
+
+        let messages: Vec<&Message> = app_state_message.previous_messages.iter().collect();
+        frame.render_widget(Clear, area);
+        render_virtualized_list(
+            frame,
+            area,
+            block,
+            &messages,
+            |message| {
+                // multi-line items would break the virtualization
+                let text = message.text.replace('\n', " ");
+                ListItem::new(format!(
+                    "{} [{}] {}",
+                    message.time.format("%H:%M:%S"),
+                    message.level.get_display_name(),
+                    text
+                ))
+                .style(get_message_style(message.level))
+            },
+            &mut app_state.message_history_list_state,
+        );
+    }
+
+    if matches!(
+        app_state.input_action,
+        InputAction::BulkRenameFind
+            | InputAction::BulkRenameReplace
+            | InputAction::BulkRenameConfirm
+    ) {
+        draw_bulk_rename_preview(frame, app_state);
+    }
+}
+
+/// Draws the completion candidates right above the input
+fn draw_path_completion_popup(
+    frame: &mut Frame,
+    input_area: Rect,
+    path_completion: &mut PathCompletion,
+) {
+    let height = (path_completion.candidates.len() as u16).min(10) + 2;
+    let area = Rect {
+        x: input_area.x,
+        y: input_area.y.saturating_sub(height),
+        width: input_area.width / 2,
+        height: height.min(input_area.y),
+    };
+
+    frame.render_widget(Clear, area);
+    render_virtualized_list(
+        frame,
+        area,
+        Block::bordered().title("Completions"),
+        &path_completion.candidates,
+        |candidate| {
+            // only the name, the typed directory part is the same for every candidate
+            let name_start = candidate
+                .trim_end_matches('/')
+                .rfind('/')
+                .map_or(0, |index| index + 1);
+            ListItem::new(&candidate[name_start..])
+        },
+        &mut path_completion.list_state,
+    );
+}
+
+fn get_message_style(level: MessageLevel) -> Style {
+    match level {
+        MessageLevel::Info => Style::new(),
+        MessageLevel::Success => Style::new().light_green(),
+        MessageLevel::Warning => Style::new().yellow(),
+        MessageLevel::Error => Style::new().light_red().bold(),
+    }
+}
+
+/// Draws the most recent watcher events matching the filter, the newest at the bottom
+fn draw_event_log(frame: &mut Frame, area: Rect, app_state: &AppState) {
+    let entries = app_state
+        .event_log
+        .get_filtered_entries(app_state.event_log_filter);
+    let block = Block::bordered()
+        .title(format!(
+            "Filesystem events (filter: {})",
+            app_state.event_log_filter.get_display_name()
+        ))
+        .title_bottom(Line::from("F to cycle the filter, E to close").right_aligned());
+
+    let visible_height = block.inner(area).height as usize;
+    let items: Vec<ListItem> = entries
+        .iter()
+        .skip(entries.len().saturating_sub(visible_height))
+        .map(|entry| ListItem::new(entry.format()))
+        .collect();
+
+    frame.render_widget(List::new(items).block(block), area);
+}
+
+fn draw_bulk_rename_preview(frame: &mut Frame, app_state: &mut AppState) {
+    // While the user is still typing the regex, just show which files would be matched
+    let (find, replace) = match app_state.input_action {
+        InputAction::BulkRenameFind => (app_state.user_input.as_str().to_string(), "$0".into()),
+        InputAction::BulkRenameReplace => (
+            app_state.bulk_rename_find.clone(),
+            app_state.user_input.as_str().to_string(),
+        ),
+        _ => (
+            app_state.bulk_rename_find.clone(),
+            app_state.bulk_rename_replace.clone(),
+        ),
+    };
+
+    let area = popup_area(frame.area(), 80, 60);
+    frame.render_widget(Clear, area);
+
+    let preview = get_bulk_rename_preview(
+        &mut app_state.bulk_rename_preview,
+        &app_state.selected_files,
+        &find,
+        &replace,
+    );
+    match &preview.entries {
+        Ok(entries) => {
+            let conflict_count = entries.iter().filter(|entry| entry.has_conflict).count();
+            let rename_count = entries.iter().filter(|entry| !entry.is_unchanged()).count();
+
+            let rows: Vec<Row> = entries
+                .iter()
+                .map(|entry| {
+                    let style = if entry.has_conflict {
+                        Style::new().red().bold()
+                    } else if entry.is_unchanged() {
+                        Style::new().dark_gray()
+                    } else {
+                        Style::new()
+                    };
+                    Row::new(vec![
+                        Cell::from(entry.old_path.clone()),
+                        Cell::from(entry.new_path.clone()),
+                    ])
+                    .style(style)
+                })
+                .collect();
+
+            let table = Table::new(
+                rows,
+                [Constraint::Percentage(50), Constraint::Percentage(50)],
+            )
+            .header(Row::new(vec!["Old", "New"]).style(Style::new().bold().light_green()))
+            .block(Block::bordered().title(format!(
+                "Bulk rename preview ({} to rename, {} conflicts)",
+                rename_count, conflict_count
+            )));
+            frame.render_widget(table, area);
+        }
+        Err(error) => {
+            let paragraph = Paragraph::new(format!("Invalid regex: {}", error))
+                .red()
+                .block(Block::bordered().title("Bulk rename preview"));
+            frame.render_widget(paragraph, area);
+        }
+    }
+}
+
+/// Creates the list item of a file in the 'Files' window. If a name column width is given, the
+/// size and modification time read when the file was listed are shown next to the name.
+fn get_file_list_item<'a>(
+    file: &'a File,
+    name_column_width: Option<usize>,
+    recent_changes: &RecentChanges,
+) -> ListItem<'a> {
+    let style = recent_changes
+        .get_highlight_stage(&file.full_path)
+        .map(|stage| CHANGED_FILE_STYLES[stage as usize])
+        .unwrap_or_default();
+
+    let Some(name_column_width) = name_column_width else {
+        return ListItem::new(file.display_name.as_str()).style(style);
+    };
+
+    let size = if file.is_dir {
+        String::new()
+    } else {
+        format_file_size(file.size)
+    };
+    let modified = file
+        .modified
+        .map(|modified| {
+            DateTime::<Local>::from(modified)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default();
+
+    let line = Line::from(vec![
+        Span::raw(fit_to_width(&file.display_name, name_column_width)),
+        Span::raw(format!("  {:>SIZE_COLUMN_WIDTH$}", size)),
+        Span::raw(format!("  {:>MODIFIED_COLUMN_WIDTH$}", modified)).dark_gray(),
+    ]);
+    ListItem::new(line).style(style)
+}
+
+/// Truncates the text to the given display width, marking the cut with an ellipsis, or pads it
+/// with spaces
+fn fit_to_width(text: &str, width: usize) -> String {
+    let text_width = text.width();
+    if text_width <= width {
+        return text.to_string() + &" ".repeat(width - text_width);
+    }
+
+    let mut fitted = String::new();
+    let mut fitted_width = 0;
+    for char in text.chars() {
+        let char_width = char.width().unwrap_or(0);
+        // leave room for the ellipsis
+        if fitted_width + char_width + 1 > width {
+            break;
+        }
+        fitted.push(char);
+        fitted_width += char_width;
+    }
+    fitted.push('…');
+    fitted + &" ".repeat(width.saturating_sub(fitted_width + 1))
+}
+
+/// Renders a list with a scrollbar and an "index/total" position indicator. Only the visible
+/// items are turned into list items, so lists with hundreds of thousands of items stay fast.
+/// The offset of the list state is used as the scroll position.
+fn render_virtualized_list<'a, T>(
+    frame: &mut Frame,
+    area: Rect,
+    block: Block,
+    items: &'a [T],
+    to_list_item: impl Fn(&'a T) -> ListItem<'a>,
+    state: &mut ListState,
+) {
+    let visible_height = block.inner(area).height as usize;
+    let item_count = items.len();
+
+    // clamp the selection, e.g. select_next doesn't know how many items there are
+    let selected_index = state
+        .selected()
+        .map(|index| index.min(item_count.saturating_sub(1)));
+    if item_count != 0 {
+        state.select(selected_index);
+    }
+
+    // scroll just enough to keep the selected item visible
+    let mut offset = state.offset().min(item_count.saturating_sub(1));
+    if let Some(selected_index) = selected_index {
+        if selected_index < offset {
+            offset = selected_index;
+        } else if visible_height != 0 && selected_index >= offset + visible_height {
+            offset = selected_index + 1 - visible_height;
+        }
+    }
+    *state.offset_mut() = offset;
+
+    let position_indicator = match selected_index {
+        Some(selected_index) if item_count != 0 => {
+            format!("{}/{}", selected_index + 1, item_count)
+        }
+        _ => format!("0/{}", item_count),
+    };
+    let block = block.title_bottom(Line::from(position_indicator).right_aligned());
+
+    let visible_end = (offset + visible_height).min(item_count);
+    let visible_items: Vec<ListItem> = items[offset..visible_end]
+        .iter()
+        .map(to_list_item)
+        .collect();
+    let mut visible_list_state =
+        ListState::default().with_selected(selected_index.map(|index| index - offset));
+
+    let list = List::new(visible_items)
+        .block(block)
+        .highlight_style(SELECTED_STYLE)
+        .highlight_symbol(">");
+    frame.render_stateful_widget(list, area, &mut visible_list_state);
+
+    if item_count > visible_height {
+        let mut scrollbar_state = ScrollbarState::new(item_count)
+            .position(selected_index.unwrap_or(0))
+            .viewport_content_length(visible_height);
+        let scrollbar_area = area.inner(Margin {
+            vertical: 1,
+            horizontal: 0,
+        });
+        frame.render_stateful_widget(
+            Scrollbar::new(ScrollbarOrientation::VerticalRight),
+            scrollbar_area,
+            &mut scrollbar_state,
+        );
+    }
 }
 
 pub fn reset_input(app_state: &mut AppState) {
-    app_state.user_input = "".to_string();
+    app_state.user_input.clear();
+    app_state.prompted_file = None;
+    app_state.input_history.reset_navigation();
     app_state.input_action = InputAction::None;
 }
 
 pub fn reset_current_message_and_input(app_state: &mut AppState) {
-    app_state.user_input = "".to_string();
+    app_state.user_input.clear();
+    app_state.prompted_file = None;
+    app_state.input_history.reset_navigation();
     app_state.input_action = InputAction::None;
-    send_message_or_panic(&mut app_state.sender_for_ui_message, "".to_string());
+    send_message_or_panic(&mut app_state.app_event_sender, "".to_string());
 }
 
 pub fn add_char_input(new_char: char, app_state: &mut AppState) {
-    let input_length = app_state.user_input.len();
-    app_state.user_input.insert(input_length, new_char);
+    app_state.user_input.insert_char(new_char);
 }
 
 pub fn handle_backspace(app_state: &mut AppState) {
     if app_state.input_action == InputAction::None {
         return;
     }
-    app_state.user_input.pop();
+    app_state.user_input.delete_char_before_cursor();
 }
 
-pub fn get_selected_item_from_list_state<'a>(state: &ListState, list: &'a [File]) -> &'a File {
-    let selected_index = state.selected().expect("something should be selected");
-    let selected_item = list
-        .get(selected_index)
-        .expect("given list actually contains item from given index");
-    selected_item
+/// Returns the item under the cursor. None if nothing is selected or the item isn't loaded yet,
+/// e.g. while a directory is being listed.
+pub fn get_selected_item_from_list_state<'a>(
+    state: &ListState,
+    list: &'a [File],
+) -> Option<&'a File> {
+    state
+        .selected()
+        .and_then(|selected_index| list.get(selected_index))
 }
 
 /// helper function to create a centered rect using up certain percentage of the available rect `r`